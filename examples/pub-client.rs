@@ -74,7 +74,7 @@ fn main() {
     let channel_filters: Vec<(TopicFilter, QualityOfService)> = matches
         .values_of("SUBSCRIBE")
         .unwrap()
-        .map(|c| (TopicFilter::new(c.to_string()).unwrap(), QualityOfService::Level0))
+        .map(|c| (c.parse::<TopicFilter>().unwrap(), QualityOfService::Level0))
         .collect();
 
     info!("Connecting to {:?} ... ", server_addr);
@@ -107,7 +107,7 @@ fn main() {
     let channels: Vec<TopicName> = matches
         .values_of("SUBSCRIBE")
         .unwrap()
-        .map(|c| TopicName::new(c.to_string()).unwrap())
+        .map(|c| c.parse::<TopicName>().unwrap())
         .collect();
 
     let user_name = matches.value_of("USER_NAME").unwrap_or("<anonym>");
@@ -122,7 +122,7 @@ fn main() {
                     continue;
                 }
             };
-            trace!("PACKET {:?}", packet);
+            trace!("PACKET {}", packet);
 
             match packet {
                 VariablePacket::PingreqPacket(..) => {
@@ -1,7 +1,6 @@
 use std::env;
 use std::io::Write;
 use std::net;
-use std::str;
 use std::time::Duration;
 
 use clap::{App, Arg};
@@ -9,8 +8,8 @@ use log::{error, info, trace};
 
 use uuid::Uuid;
 
-use tokio::io::AsyncWriteExt;
 use tokio::net::TcpStream;
+use tokio::time::Instant;
 
 use mqtt::control::variable_header::ConnectReturnCode;
 use mqtt::packet::*;
@@ -77,7 +76,7 @@ async fn main() {
     let channel_filters: Vec<(TopicFilter, QualityOfService)> = matches
         .values_of("SUBSCRIBE")
         .unwrap()
-        .map(|c| (TopicFilter::new(c.to_string()).unwrap(), QualityOfService::Level0))
+        .map(|c| (c.parse::<TopicFilter>().unwrap(), QualityOfService::Level0))
         .collect();
 
     let keep_alive = 10;
@@ -119,7 +118,7 @@ async fn main() {
                 continue;
             }
         };
-        trace!("PACKET {:?}", packet);
+        trace!("PACKET {}", packet);
 
         if let VariablePacket::SubackPacket(ref ack) = packet {
             if ack.packet_identifier() != 10 {
@@ -140,26 +139,36 @@ async fn main() {
         loop {
             info!("Sending PINGREQ to broker");
 
-            let pingreq_packet = PingreqPacket::new();
-
-            let mut buf = Vec::new();
-            pingreq_packet.encode(&mut buf).unwrap();
-            mqtt_write.write_all(&buf).await.unwrap();
+            let pingreq_packet = VariablePacket::new(PingreqPacket::new());
+            pingreq_packet.write_to(&mut mqtt_write).await.unwrap();
 
             tokio::time::sleep(Duration::from_secs(keep_alive as u64 / 2)).await;
         }
     };
 
     let receiver = async move {
-        while let Ok(packet) = VariablePacket::parse(&mut mqtt_read).await {
-            trace!("PACKET {:?}", packet);
+        loop {
+            // PINGREQ is sent every keep_alive / 2 seconds, so the broker answers well before this
+            let deadline = Instant::now() + Duration::from_secs(keep_alive as u64);
+            let packet = match VariablePacket::parse_with_deadline(&mut mqtt_read, deadline).await {
+                Ok(packet) => packet,
+                Err(VariablePacketError::Timeout) => {
+                    error!("No packet received from broker in {}s, closing", keep_alive);
+                    break;
+                }
+                Err(err) => {
+                    error!("Error in receiving packet {}", err);
+                    break;
+                }
+            };
+            trace!("PACKET {}", packet);
 
             match packet {
                 VariablePacket::PingrespPacket(..) => {
                     info!("Received PINGRESP from broker ..");
                 }
                 VariablePacket::PublishPacket(ref publ) => {
-                    let msg = match str::from_utf8(publ.payload()) {
+                    let msg = match publ.payload_str() {
                         Ok(msg) => msg,
                         Err(err) => {
                             error!("Failed to decode publish message {:?}", err);
@@ -176,5 +185,9 @@ async fn main() {
     tokio::pin!(ping_sender);
     tokio::pin!(receiver);
 
-    tokio::join!(ping_sender, receiver);
+    // Stop sending PINGREQ as soon as the connection is gone
+    tokio::select! {
+        _ = ping_sender => {}
+        _ = receiver => {}
+    }
 }
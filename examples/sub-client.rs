@@ -6,9 +6,7 @@ extern crate env_logger;
 extern crate uuid;
 
 use std::env;
-use std::io::Write;
 use std::net::TcpStream;
-use std::str;
 use std::thread;
 use std::time::{Duration, Instant};
 
@@ -18,8 +16,8 @@ use uuid::Uuid;
 
 use mqtt::control::variable_header::ConnectReturnCode;
 use mqtt::packet::*;
+use mqtt::QualityOfService;
 use mqtt::TopicFilter;
-use mqtt::{Decodable, Encodable, QualityOfService};
 
 fn generate_client_id() -> String {
     format!("/MQTT/rust/{}", Uuid::new_v4())
@@ -80,24 +78,25 @@ fn main() {
     let channel_filters: Vec<(TopicFilter, QualityOfService)> = matches
         .values_of("SUBSCRIBE")
         .unwrap()
-        .map(|c| (TopicFilter::new(c.to_string()).unwrap(), QualityOfService::Level0))
+        .map(|c| (c.parse::<TopicFilter>().unwrap(), QualityOfService::Level0))
         .collect();
 
     let keep_alive = 10;
 
     info!("Connecting to {:?} ... ", server_addr);
-    let mut stream = TcpStream::connect(server_addr).unwrap();
+    let stream = TcpStream::connect(server_addr).unwrap();
     info!("Connected!");
 
+    let mut writer = PacketWriter::new(stream.try_clone().unwrap());
+    let mut reader = PacketReader::new(stream);
+
     info!("Client identifier {:?}", client_id);
     let mut conn = ConnectPacket::new(client_id);
     conn.set_clean_session(true);
     conn.set_keep_alive(keep_alive);
-    let mut buf = Vec::new();
-    conn.encode(&mut buf).unwrap();
-    stream.write_all(&buf[..]).unwrap();
+    writer.write_packet(&conn).unwrap();
 
-    let connack = ConnackPacket::decode(&mut stream).unwrap();
+    let connack = reader.read_packet().unwrap().into_connack().unwrap();
     trace!("CONNACK {:?}", connack);
 
     if connack.connect_return_code() != ConnectReturnCode::ConnectionAccepted {
@@ -110,19 +109,17 @@ fn main() {
     // const CHANNEL_FILTER: &'static str = "typing-speed-test.aoeu.eu";
     info!("Applying channel filters {:?} ...", channel_filters);
     let sub = SubscribePacket::new(10, channel_filters);
-    let mut buf = Vec::new();
-    sub.encode(&mut buf).unwrap();
-    stream.write_all(&buf[..]).unwrap();
+    writer.write_packet(&sub).unwrap();
 
     loop {
-        let packet = match VariablePacket::decode(&mut stream) {
+        let packet = match reader.read_packet() {
             Ok(pk) => pk,
             Err(err) => {
                 error!("Error in receiving packet {:?}", err);
                 continue;
             }
         };
-        trace!("PACKET {:?}", packet);
+        trace!("PACKET {}", packet);
 
         if let VariablePacket::SubackPacket(ref ack) = packet {
             if ack.packet_identifier() != 10 {
@@ -134,7 +131,6 @@ fn main() {
         }
     }
 
-    let mut stream_clone = stream.try_clone().unwrap();
     thread::spawn(move || {
         let mut last_ping_time = Instant::now();
         let mut next_ping_time = last_ping_time + Duration::from_secs((keep_alive as f32 * 0.9) as u64);
@@ -143,11 +139,7 @@ fn main() {
             if keep_alive > 0 && current_timestamp >= next_ping_time {
                 info!("Sending PINGREQ to broker");
 
-                let pingreq_packet = PingreqPacket::new();
-
-                let mut buf = Vec::new();
-                pingreq_packet.encode(&mut buf).unwrap();
-                stream_clone.write_all(&buf[..]).unwrap();
+                writer.write_packet(&PingreqPacket::new()).unwrap();
 
                 last_ping_time = current_timestamp;
                 next_ping_time = last_ping_time + Duration::from_secs((keep_alive as f32 * 0.9) as u64);
@@ -157,21 +149,25 @@ fn main() {
     });
 
     loop {
-        let packet = match VariablePacket::decode(&mut stream) {
+        let packet = match reader.read_packet() {
             Ok(pk) => pk,
+            Err(VariablePacketError::IncompletePacket { .. }) | Err(VariablePacketError::IoError(..)) => {
+                error!("Connection closed by broker");
+                break;
+            }
             Err(err) => {
                 error!("Error in receiving packet {}", err);
                 continue;
             }
         };
-        trace!("PACKET {:?}", packet);
+        trace!("PACKET {}", packet);
 
         match packet {
             VariablePacket::PingrespPacket(..) => {
                 info!("Receiving PINGRESP from broker ..");
             }
             VariablePacket::PublishPacket(ref publ) => {
-                let msg = match str::from_utf8(publ.payload()) {
+                let msg = match publ.payload_str() {
                     Ok(msg) => msg,
                     Err(err) => {
                         error!("Failed to decode publish message {:?}", err);
@@ -1,15 +1,19 @@
 //! Fixed header in MQTT
 
-use std::io::{self, Read, Write};
-
-use byteorder::{ReadBytesExt, WriteBytesExt};
-
 #[cfg(feature = "tokio")]
-use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 use crate::control::packet_type::{PacketType, PacketTypeError};
+use crate::control::variable_header::ProtocolLevel;
+use crate::encodable::{ReadBytesExt, WriteBytesExt};
+use crate::io::{self, BufRead, Read, Write};
 use crate::{Decodable, Encodable};
 
+/// Maximum value of the remaining length field, which is encoded in at most 4 bytes
+///
+/// <http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718023>
+pub const MAX_REMAINING_LENGTH: u32 = 268_435_455;
+
 /// Fixed header for each MQTT control packet
 ///
 /// Format:
@@ -22,7 +26,8 @@ use crate::{Decodable, Encodable};
 /// | Remaining Length ...                                |
 /// +-----------------------------------------------------+
 /// ```
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FixedHeader {
     /// Packet Type
     pub packet_type: PacketType,
@@ -35,13 +40,84 @@ pub struct FixedHeader {
 
 impl FixedHeader {
     pub fn new(packet_type: PacketType, remaining_length: u32) -> FixedHeader {
-        debug_assert!(remaining_length <= 0x0FFF_FFFF);
+        debug_assert!(remaining_length <= MAX_REMAINING_LENGTH);
         FixedHeader {
             packet_type,
             remaining_length,
         }
     }
 
+    /// Decodes a fixed header from the beginning of `data`, returning it with the number of bytes
+    /// it takes. Returns `None` if `data` ends before the fixed header does.
+    ///
+    /// This allows framing packets in a buffer without blocking on a stream, the body of the
+    /// packet is the `remaining_length` bytes that follow.
+    pub fn decode_from_slice(data: &[u8]) -> Option<Result<(FixedHeader, usize), FixedHeaderError>> {
+        let mut rdr = data;
+        match FixedHeader::decode(&mut rdr) {
+            Ok(header) => Some(Ok((header, data.len() - rdr.len()))),
+            Err(FixedHeaderError::IoError(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+
+    /// Decodes a fixed header from the buffer of `rdr`
+    ///
+    /// `decode` reads the header one byte at a time, which is a system call per byte on an
+    /// unbuffered `TcpStream`. This takes the header bytes from the buffer of a `BufRead` such as
+    /// `std::io::BufReader` instead, and only falls back to reading byte by byte when the header
+    /// is split across two fills of the buffer.
+    pub fn decode_buffered<R: BufRead>(rdr: &mut R) -> Result<FixedHeader, FixedHeaderError> {
+        let buf = rdr.fill_buf()?;
+        let mut data = buf;
+        let result = FixedHeader::decode(&mut data);
+        let consumed = buf.len() - data.len();
+
+        match result {
+            Err(FixedHeaderError::IoError(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                FixedHeader::decode(rdr)
+            }
+            result => {
+                rdr.consume(consumed);
+                result
+            }
+        }
+    }
+
+    /// Decodes a fixed header for the given protocol level, which accepts the `AUTH` type in MQTT 5
+    ///
+    /// The header is read one byte at a time, so `rdr` should be buffered, see `decode_buffered`.
+    pub fn decode_with_level<R: Read>(rdr: &mut R, level: ProtocolLevel) -> Result<FixedHeader, FixedHeaderError> {
+        let type_val = rdr.read_u8()?;
+        let remaining_len = {
+            let mut cur = 0u32;
+            for i in 0.. {
+                let byte = rdr.read_u8()?;
+                cur |= ((byte as u32) & 0x7F) << (7 * i);
+
+                if i >= 4 {
+                    return Err(FixedHeaderError::MalformedRemainingLength);
+                }
+
+                if byte & 0x80 == 0 {
+                    // A zero last byte could have been left out [MQTT-1.5.5-1]
+                    if byte == 0 && i > 0 {
+                        return Err(FixedHeaderError::MalformedRemainingLength);
+                    }
+                    break;
+                }
+            }
+
+            cur
+        };
+
+        match PacketType::from_u8_with_level(type_val, level) {
+            Ok(packet_type) => Ok(FixedHeader::new(packet_type, remaining_len)),
+            Err(PacketTypeError::ReservedType(ty, _)) => Err(FixedHeaderError::ReservedType(ty, remaining_len)),
+            Err(err) => Err(From::from(err)),
+        }
+    }
+
     #[cfg(feature = "tokio")]
     /// Asynchronously parse a single fixed header from an AsyncRead type, such as a network
     /// socket.
@@ -63,6 +139,10 @@ impl FixedHeader {
             }
 
             if byte & 0x80 == 0 {
+                // A zero last byte could have been left out [MQTT-1.5.5-1]
+                if byte == 0 && i > 0 {
+                    return Err(FixedHeaderError::MalformedRemainingLength);
+                }
                 break;
             } else {
                 i += 1;
@@ -75,6 +155,18 @@ impl FixedHeader {
             Err(err) => Err(From::from(err)),
         }
     }
+
+    #[cfg(feature = "tokio")]
+    /// Asynchronously write this fixed header to an AsyncWrite type, such as a network socket.
+    ///
+    /// This requires mqtt-rs to be built with `feature = "tokio"`
+    pub async fn write_to<A: AsyncWrite + Unpin>(&self, wr: &mut A) -> io::Result<()> {
+        // A fixed header takes at most 5 bytes, so it is encoded on the stack
+        let mut buf = [0u8; 5];
+        let len = self.encoded_length() as usize;
+        self.encode(&mut &mut buf[..])?;
+        wr.write_all(&buf[..len]).await
+    }
 }
 
 impl Encodable for FixedHeader {
@@ -101,16 +193,20 @@ impl Encodable for FixedHeader {
     }
 
     fn encoded_length(&self) -> u32 {
-        let rem_size = if self.remaining_length >= 2_097_152 {
-            4
-        } else if self.remaining_length >= 16_384 {
-            3
-        } else if self.remaining_length >= 128 {
-            2
-        } else {
-            1
-        };
-        1 + rem_size
+        1 + remaining_length_size(self.remaining_length)
+    }
+}
+
+/// Number of bytes of the minimal encoding of `remaining_length`, the only one accepted on decode
+pub(crate) fn remaining_length_size(remaining_length: u32) -> u32 {
+    if remaining_length >= 2_097_152 {
+        4
+    } else if remaining_length >= 16_384 {
+        3
+    } else if remaining_length >= 128 {
+        2
+    } else {
+        1
     }
 }
 
@@ -119,30 +215,7 @@ impl Decodable for FixedHeader {
     type Cond = ();
 
     fn decode_with<R: Read>(rdr: &mut R, _rest: ()) -> Result<FixedHeader, FixedHeaderError> {
-        let type_val = rdr.read_u8()?;
-        let remaining_len = {
-            let mut cur = 0u32;
-            for i in 0.. {
-                let byte = rdr.read_u8()?;
-                cur |= ((byte as u32) & 0x7F) << (7 * i);
-
-                if i >= 4 {
-                    return Err(FixedHeaderError::MalformedRemainingLength);
-                }
-
-                if byte & 0x80 == 0 {
-                    break;
-                }
-            }
-
-            cur
-        };
-
-        match PacketType::from_u8(type_val) {
-            Ok(packet_type) => Ok(FixedHeader::new(packet_type, remaining_len)),
-            Err(PacketTypeError::ReservedType(ty, _)) => Err(FixedHeaderError::ReservedType(ty, remaining_len)),
-            Err(err) => Err(From::from(err)),
-        }
+        FixedHeader::decode_with_level(rdr, ProtocolLevel::Version311)
     }
 }
 
@@ -158,11 +231,11 @@ pub enum FixedHeaderError {
     IoError(#[from] io::Error),
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
 
-    use crate::control::packet_type::{ControlType, PacketType};
+    use crate::control::packet_type::{ControlType, InvalidFlag, PacketType};
     use crate::{Decodable, Encodable};
     use std::io::Cursor;
 
@@ -185,6 +258,139 @@ mod test {
         assert_eq!(header.remaining_length, 321);
     }
 
+    #[test]
+    fn test_decode_fixed_header_reserved_flags() {
+        // PUBREL, SUBSCRIBE and UNSUBSCRIBE must carry the flags 0b0010 [MQTT-3.6.1-1] [MQTT-3.8.1-1]
+        // [MQTT-3.10.1-1]
+        for &(byte, control_type) in &[
+            (0x62, ControlType::PublishRelease),
+            (0x82, ControlType::Subscribe),
+            (0xA2, ControlType::Unsubscribe),
+        ] {
+            let header = FixedHeader::decode(&mut Cursor::new([byte, 0x00])).unwrap();
+            assert_eq!(header.packet_type.control_type(), control_type);
+
+            for &flags in &[0b0000, 0b0011, 0b1010] {
+                match FixedHeader::decode(&mut Cursor::new([byte & 0xF0 | flags, 0x00])) {
+                    Err(FixedHeaderError::PacketTypeError(PacketTypeError::InvalidFlag(InvalidFlag(t, f))))
+                        if t == control_type && f == flags => {}
+                    res => panic!("unexpected result {:?}", res),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_fixed_header_buffered() {
+        use std::io::{BufRead, BufReader, Read};
+
+        let mut rdr = Cursor::new(&b"\x10\xc1\x02\x00\x04"[..]);
+        let header = FixedHeader::decode_buffered(&mut rdr).unwrap();
+        assert_eq!(header.packet_type, PacketType::with_default(ControlType::Connect));
+        assert_eq!(header.remaining_length, 321);
+        assert_eq!(rdr.position(), 3);
+
+        // The header is split across two fills of the buffer
+        let mut rdr = BufReader::with_capacity(2, &b"\x10\xc1\x02\x00\x04"[..]);
+        rdr.fill_buf().unwrap();
+        assert_eq!(FixedHeader::decode_buffered(&mut rdr).unwrap(), header);
+        let mut rest = Vec::new();
+        rdr.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"\x00\x04");
+
+        // Reserved types consume the header like `decode`
+        let mut rdr = Cursor::new(&b"\x00\x01\xFF"[..]);
+        assert!(matches!(
+            FixedHeader::decode_buffered(&mut rdr),
+            Err(FixedHeaderError::ReservedType(0, 1))
+        ));
+        assert_eq!(rdr.position(), 2);
+
+        assert!(matches!(
+            FixedHeader::decode_buffered(&mut Cursor::new(&b""[..])),
+            Err(FixedHeaderError::IoError(..))
+        ));
+    }
+
+    #[test]
+    fn test_decode_fixed_header_from_slice() {
+        let (header, size) = FixedHeader::decode_from_slice(b"\x10\xc1\x02\x00\x04")
+            .unwrap()
+            .unwrap();
+        assert_eq!(header.packet_type, PacketType::with_default(ControlType::Connect));
+        assert_eq!(header.remaining_length, 321);
+        assert_eq!(size, 3);
+
+        assert!(FixedHeader::decode_from_slice(b"").is_none());
+        assert!(FixedHeader::decode_from_slice(b"\x10\xc1").is_none());
+        assert!(matches!(
+            FixedHeader::decode_from_slice(b"\x10\x80\x80\x80\x80\x02"),
+            Some(Err(FixedHeaderError::MalformedRemainingLength))
+        ));
+    }
+
+    #[test]
+    fn test_encode_max_remaining_length() {
+        assert_eq!(MAX_REMAINING_LENGTH, 0x0FFF_FFFF);
+
+        let header = FixedHeader::new(PacketType::with_default(ControlType::Publish), MAX_REMAINING_LENGTH);
+        let mut buf = Vec::new();
+        header.encode(&mut buf).unwrap();
+
+        assert_eq!(&buf[..], b"\x30\xff\xff\xff\x7f");
+        assert_eq!(header.encoded_length(), 5);
+    }
+
+    #[test]
+    fn test_decode_fixed_header_non_minimal_length() {
+        // 0 encoded in two bytes
+        assert!(matches!(
+            FixedHeader::decode(&mut &b"\x10\x80\x00"[..]),
+            Err(FixedHeaderError::MalformedRemainingLength)
+        ));
+        // 127 encoded in three bytes
+        assert!(matches!(
+            FixedHeader::decode(&mut &b"\x10\xFF\x80\x00"[..]),
+            Err(FixedHeaderError::MalformedRemainingLength)
+        ));
+
+        let header = FixedHeader::decode(&mut &b"\x10\x80\x01"[..]).unwrap();
+        assert_eq!(header.remaining_length, 128);
+    }
+
+    #[test]
+    fn test_decode_fixed_header_reserved_type() {
+        // The remaining length is still decoded, so the body can be skipped
+        assert!(matches!(
+            FixedHeader::decode(&mut &b"\x00\x82\x01"[..]),
+            Err(FixedHeaderError::ReservedType(0, 130))
+        ));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_parse_fixed_header_reserved_type() {
+        assert!(matches!(
+            FixedHeader::parse(&mut &b"\x00\x82\x01"[..]).await,
+            Err(FixedHeaderError::ReservedType(0, 130))
+        ));
+        assert!(matches!(
+            FixedHeader::parse(&mut &b"\xF0\x00"[..]).await,
+            Err(FixedHeaderError::ReservedType(15, 0))
+        ));
+    }
+
+    #[test]
+    fn test_decode_fixed_header_auth() {
+        assert!(matches!(
+            FixedHeader::decode(&mut &b"\xF0\x00"[..]),
+            Err(FixedHeaderError::ReservedType(15, 0))
+        ));
+
+        let header = FixedHeader::decode_with_level(&mut &b"\xF0\x00"[..], ProtocolLevel::Version50).unwrap();
+        assert_eq!(header.packet_type.control_type(), ControlType::Auth);
+    }
+
     #[test]
     #[should_panic]
     fn test_decode_too_long_fixed_header() {
@@ -1,16 +1,20 @@
 //! Packet types
 
+use crate::control::variable_header::ProtocolLevel;
 use crate::qos::QualityOfService;
 
 /// Packet type
 // INVARIANT: the high 4 bits of the byte must be a valid control type
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
 pub struct PacketType(u8);
 
 /// Defined control types
+///
+/// More types may be added as later protocol versions are supported, as `Auth` was for MQTT 5.
 #[rustfmt::skip]
 #[repr(u8)]
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[non_exhaustive]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
 pub enum ControlType {
     /// Client request to connect to Server
     Connect                         = value::CONNECT,
@@ -53,6 +57,9 @@ pub enum ControlType {
 
     /// Client is disconnecting
     Disconnect                      = value::DISCONNECT,
+
+    /// Authentication exchange, MQTT 5 only
+    Auth                            = value::AUTH,
 }
 
 impl ControlType {
@@ -78,6 +85,8 @@ impl ControlType {
             ControlType::PingResponse => 0,
 
             ControlType::Disconnect => 0,
+
+            ControlType::Auth => 0,
         }
     }
 }
@@ -91,7 +100,7 @@ impl PacketType {
         let flags_ok = match t {
             ControlType::Publish => {
                 let qos = (flags & 0b0110) >> 1;
-                matches!(qos, 0 | 1 | 2)
+                matches!(qos, 0..=2)
             }
             _ => t.default_flags() == flags,
         };
@@ -137,12 +146,23 @@ impl PacketType {
         self.0
     }
 
-    /// From code
+    /// From code, as defined in MQTT 3.1.1
     pub fn from_u8(val: u8) -> Result<PacketType, PacketTypeError> {
+        PacketType::from_u8_with_level(val, ProtocolLevel::Version311)
+    }
+
+    /// From code, for the given protocol level
+    ///
+    /// Type 0 is always reserved, while type 15 is `AUTH` in MQTT 5 and reserved in earlier versions.
+    pub fn from_u8_with_level(val: u8, level: ProtocolLevel) -> Result<PacketType, PacketTypeError> {
         let type_val = val >> 4;
         let flags = val & 0x0F;
 
-        let control_type = get_control_type(type_val).ok_or(PacketTypeError::ReservedType(type_val, flags))?;
+        let control_type = match get_control_type(type_val) {
+            Some(ControlType::Auth) if level != ProtocolLevel::Version50 => None,
+            control_type => control_type,
+        };
+        let control_type = control_type.ok_or(PacketTypeError::ReservedType(type_val, flags))?;
         Ok(PacketType::new(control_type, flags)?)
     }
 
@@ -150,7 +170,7 @@ impl PacketType {
     pub fn control_type(self) -> ControlType {
         get_control_type(self.0 >> 4).unwrap_or_else(|| {
             // SAFETY: this is maintained by the invariant for PacketType
-            unsafe { std::hint::unreachable_unchecked() }
+            unsafe { core::hint::unreachable_unchecked() }
         })
     }
 
@@ -160,6 +180,23 @@ impl PacketType {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for PacketType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.to_u8())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PacketType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<PacketType, D::Error> {
+        // Validated, to keep the invariant of the control type. The packets of this crate are all
+        // MQTT 3.1.1 ones, so `AUTH` is rejected like when decoding them.
+        let val = <u8 as serde::Deserialize>::deserialize(deserializer)?;
+        PacketType::from_u8(val).map_err(serde::de::Error::custom)
+    }
+}
+
 #[inline]
 fn get_control_type(val: u8) -> Option<ControlType> {
     let typ = match val {
@@ -182,6 +219,7 @@ fn get_control_type(val: u8) -> Option<ControlType> {
         value::PINGRESP => ControlType::PingResponse,
 
         value::DISCONNECT => ControlType::Disconnect,
+        value::AUTH => ControlType::Auth,
 
         _ => return None,
     };
@@ -217,4 +255,33 @@ mod value {
     pub const PINGREQ:     u8 = 12;
     pub const PINGRESP:    u8 = 13;
     pub const DISCONNECT:  u8 = 14;
+    pub const AUTH:        u8 = 15;
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_packet_type_reserved() {
+        assert!(matches!(
+            PacketType::from_u8(0x00),
+            Err(PacketTypeError::ReservedType(0, 0))
+        ));
+        assert!(matches!(
+            PacketType::from_u8(0xF0),
+            Err(PacketTypeError::ReservedType(15, 0))
+        ));
+
+        let auth = PacketType::from_u8_with_level(0xF0, ProtocolLevel::Version50).unwrap();
+        assert_eq!(auth.control_type(), ControlType::Auth);
+        assert!(matches!(
+            PacketType::from_u8_with_level(0x00, ProtocolLevel::Version50),
+            Err(PacketTypeError::ReservedType(0, 0))
+        ));
+        assert!(matches!(
+            PacketType::from_u8_with_level(0xF1, ProtocolLevel::Version50),
+            Err(PacketTypeError::InvalidFlag(..))
+        ));
+    }
 }
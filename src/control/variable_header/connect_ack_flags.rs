@@ -1,12 +1,11 @@
-use std::io::{self, Read, Write};
-
-use byteorder::{ReadBytesExt, WriteBytesExt};
-
 use crate::control::variable_header::VariableHeaderError;
+use crate::encodable::{ReadBytesExt, WriteBytesExt};
+use crate::io::{self, Read, Write};
 use crate::{Decodable, Encodable};
 
 /// Flags in `CONNACK` packet
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConnackFlags {
     pub session_present: bool,
 }
@@ -1,12 +1,11 @@
-use std::io::{self, Read, Write};
-
-use byteorder::{ReadBytesExt, WriteBytesExt};
-
 use crate::control::variable_header::VariableHeaderError;
+use crate::encodable::{ReadBytesExt, WriteBytesExt};
+use crate::io::{self, Read, Write};
 use crate::{Decodable, Encodable};
 
 /// Flags for `CONNECT` packet
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConnectFlags {
     pub user_name: bool,
     pub password: bool,
@@ -60,14 +59,45 @@ impl Decodable for ConnectFlags {
             return Err(VariableHeaderError::InvalidReservedFlag);
         }
 
+        let will_qos = (code & 0b0001_1000) >> 3;
+        let will_flag = (code & 0b0000_0100) != 0;
+        if will_qos == 3 {
+            return Err(VariableHeaderError::InvalidWillQoS(will_qos));
+        }
+        if !will_flag && will_qos != 0 {
+            // Will QoS must be 0 if the Will Flag is 0 [MQTT-3.1.2-13]
+            return Err(VariableHeaderError::WillQoSWithoutWillFlag(will_qos));
+        }
+
         Ok(ConnectFlags {
             user_name: (code & 0b1000_0000) != 0,
             password: (code & 0b0100_0000) != 0,
             will_retain: (code & 0b0010_0000) != 0,
-            will_qos: (code & 0b0001_1000) >> 3,
-            will_flag: (code & 0b0000_0100) != 0,
+            will_qos,
+            will_flag,
             clean_session: (code & 0b0000_0010) != 0,
             reserved: (code & 0b0000_0001) != 0,
         })
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_connect_flags_will_qos() {
+        let flags = ConnectFlags::decode(&mut &[0b0001_0110][..]).unwrap();
+        assert!(flags.will_flag);
+        assert_eq!(flags.will_qos, 2);
+
+        assert!(matches!(
+            ConnectFlags::decode(&mut &[0b0001_1110][..]),
+            Err(VariableHeaderError::InvalidWillQoS(3))
+        ));
+        assert!(matches!(
+            ConnectFlags::decode(&mut &[0b0000_1010][..]),
+            Err(VariableHeaderError::WillQoSWithoutWillFlag(1))
+        ));
+    }
+}
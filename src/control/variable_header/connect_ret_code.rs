@@ -1,8 +1,6 @@
-use std::io::{self, Read, Write};
-
-use byteorder::{ReadBytesExt, WriteBytesExt};
-
 use crate::control::variable_header::VariableHeaderError;
+use crate::encodable::{ReadBytesExt, WriteBytesExt};
+use crate::io::{self, Read, Write};
 use crate::{Decodable, Encodable};
 
 pub const CONNECTION_ACCEPTED: u8 = 0x00;
@@ -13,7 +11,8 @@ pub const BAD_USER_NAME_OR_PASSWORD: u8 = 0x04;
 pub const NOT_AUTHORIZED: u8 = 0x05;
 
 /// Return code for `CONNACK` packet
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConnectReturnCode {
     ConnectionAccepted,
     UnacceptableProtocolVersion,
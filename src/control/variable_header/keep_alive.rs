@@ -1,12 +1,13 @@
-use std::io::{self, Read, Write};
-
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::BigEndian;
 
 use crate::control::variable_header::VariableHeaderError;
+use crate::encodable::{ReadBytesExt, WriteBytesExt};
+use crate::io::{self, Read, Write};
 use crate::{Decodable, Encodable};
 
 /// Keep alive time interval
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeepAlive(pub u16);
 
 impl Encodable for KeepAlive {
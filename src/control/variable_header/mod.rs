@@ -1,17 +1,18 @@
 //! Variable header in MQTT
 
-use std::io;
-use std::string::FromUtf8Error;
+use alloc::string::{FromUtf8Error, String};
 
+use crate::io;
 use crate::topic_name::{TopicNameDecodeError, TopicNameError};
 
 pub use self::connect_ack_flags::ConnackFlags;
 pub use self::connect_flags::ConnectFlags;
 pub use self::connect_ret_code::ConnectReturnCode;
 pub use self::keep_alive::KeepAlive;
-pub use self::packet_identifier::PacketIdentifier;
+pub use self::packet_identifier::{PacketIdentifier, ZeroPacketIdentifier};
 pub use self::protocol_level::ProtocolLevel;
 pub use self::protocol_name::ProtocolName;
+pub use self::reason_code::{NoConnectReturnCode, ReasonCode};
 pub use self::topic_name::TopicNameHeader;
 
 mod connect_ack_flags;
@@ -21,6 +22,7 @@ mod keep_alive;
 mod packet_identifier;
 pub mod protocol_level;
 mod protocol_name;
+mod reason_code;
 mod topic_name;
 
 /// Errors while decoding variable header
@@ -36,6 +38,14 @@ pub enum VariableHeaderError {
     TopicNameError(#[from] TopicNameError),
     #[error("invalid protocol version")]
     InvalidProtocolVersion,
+    #[error(transparent)]
+    ZeroPacketIdentifier(#[from] ZeroPacketIdentifier),
+    #[error("invalid will QoS {0}")]
+    InvalidWillQoS(u8),
+    #[error("will QoS {0} is set without will flag")]
+    WillQoSWithoutWillFlag(u8),
+    #[error("protocol name {0:?} does not match protocol level {1:?}")]
+    MismatchedProtocolName(String, ProtocolLevel),
 }
 
 impl From<TopicNameDecodeError> for VariableHeaderError {
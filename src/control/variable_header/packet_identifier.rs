@@ -1,14 +1,20 @@
-use std::io::{self, Read, Write};
-
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::BigEndian;
 
 use crate::control::variable_header::VariableHeaderError;
+use crate::encodable::{ReadBytesExt, WriteBytesExt};
+use crate::io::{self, Read, Write};
 use crate::{Decodable, Encodable};
 
 /// Packet identifier
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PacketIdentifier(pub u16);
 
+/// Zero packet identifier, which is invalid in every packet that carries one [MQTT-2.3.1-1]
+#[derive(Debug, Eq, PartialEq, Copy, Clone, thiserror::Error)]
+#[error("packet identifier must be non-zero")]
+pub struct ZeroPacketIdentifier;
+
 impl Encodable for PacketIdentifier {
     fn encode<W: Write>(&self, writer: &mut W) -> Result<(), io::Error> {
         writer.write_u16::<BigEndian>(self.0)
@@ -24,6 +30,10 @@ impl Decodable for PacketIdentifier {
     type Cond = ();
 
     fn decode_with<R: Read>(reader: &mut R, _rest: ()) -> Result<PacketIdentifier, VariableHeaderError> {
-        reader.read_u16::<BigEndian>().map(PacketIdentifier).map_err(From::from)
+        // Every packet that carries a packet identifier requires it to be non-zero [MQTT-2.3.1-1]
+        match reader.read_u16::<BigEndian>()? {
+            0 => Err(ZeroPacketIdentifier.into()),
+            id => Ok(PacketIdentifier(id)),
+        }
     }
 }
@@ -1,10 +1,8 @@
 //! Protocol level header
 
-use std::io::{self, Read, Write};
-
-use byteorder::{ReadBytesExt, WriteBytesExt};
-
 use crate::control::variable_header::VariableHeaderError;
+use crate::encodable::{ReadBytesExt, WriteBytesExt};
+use crate::io::{self, Read, Write};
 use crate::{Decodable, Encodable};
 
 pub const SPEC_3_1_0: u8 = 0x03;
@@ -12,7 +10,8 @@ pub const SPEC_3_1_1: u8 = 0x04;
 pub const SPEC_5_0: u8 = 0x05;
 
 /// Protocol level in MQTT (`0x04` in v3.1.1)
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum ProtocolLevel {
     Version310 = SPEC_3_1_0,
@@ -52,4 +51,12 @@ impl ProtocolLevel {
             _ => None,
         }
     }
+
+    /// Protocol name sent in `CONNECT` with this level, "MQIsdp" for v3.1 and "MQTT" since v3.1.1
+    pub fn protocol_name(self) -> &'static str {
+        match self {
+            ProtocolLevel::Version310 => "MQIsdp",
+            ProtocolLevel::Version311 | ProtocolLevel::Version50 => "MQTT",
+        }
+    }
 }
@@ -1,6 +1,7 @@
-use std::io::{self, Read, Write};
+use alloc::string::String;
 
 use crate::control::variable_header::VariableHeaderError;
+use crate::io::{self, Read, Write};
 use crate::{Decodable, Encodable};
 
 /// Protocol name in variable header
@@ -18,7 +19,8 @@ use crate::{Decodable, Encodable};
 /// | 0101                     | 0100                     | 'T'
 /// +--------------------------+--------------------------+
 /// ```
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProtocolName(pub String);
 
 impl Encodable for ProtocolName {
@@ -0,0 +1,183 @@
+use core::convert::TryFrom;
+
+use crate::control::variable_header::{ConnectReturnCode, VariableHeaderError};
+use crate::encodable::{ReadBytesExt, WriteBytesExt};
+use crate::io::{self, Read, Write};
+use crate::{Decodable, Encodable};
+
+macro_rules! reason_codes {
+    ($($name:ident = $code:expr,)*) => {
+        /// Reason code of MQTT 5 acknowledgements and `DISCONNECT`
+        ///
+        /// The value 0x00 is named `Success` here, it also stands for "Normal disconnection" and
+        /// "Granted QoS 0" depending on the packet.
+        #[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub enum ReasonCode {
+            $($name,)*
+            Reserved(u8),
+        }
+
+        impl ReasonCode {
+            /// Get the code
+            pub fn to_u8(self) -> u8 {
+                match self {
+                    $(ReasonCode::$name => $code,)*
+                    ReasonCode::Reserved(r) => r,
+                }
+            }
+
+            /// Create `ReasonCode` from code
+            pub fn from_u8(code: u8) -> ReasonCode {
+                match code {
+                    $($code => ReasonCode::$name,)*
+                    _ => ReasonCode::Reserved(code),
+                }
+            }
+        }
+    };
+}
+
+reason_codes! {
+    Success = 0x00,
+    GrantedQoS1 = 0x01,
+    GrantedQoS2 = 0x02,
+    DisconnectWithWillMessage = 0x04,
+    NoMatchingSubscribers = 0x10,
+    NoSubscriptionExisted = 0x11,
+    ContinueAuthentication = 0x18,
+    ReAuthenticate = 0x19,
+    UnspecifiedError = 0x80,
+    MalformedPacket = 0x81,
+    ProtocolError = 0x82,
+    ImplementationSpecificError = 0x83,
+    UnsupportedProtocolVersion = 0x84,
+    ClientIdentifierNotValid = 0x85,
+    BadUserNameOrPassword = 0x86,
+    NotAuthorized = 0x87,
+    ServerUnavailable = 0x88,
+    ServerBusy = 0x89,
+    Banned = 0x8A,
+    ServerShuttingDown = 0x8B,
+    BadAuthenticationMethod = 0x8C,
+    KeepAliveTimeout = 0x8D,
+    SessionTakenOver = 0x8E,
+    TopicFilterInvalid = 0x8F,
+    TopicNameInvalid = 0x90,
+    PacketIdentifierInUse = 0x91,
+    PacketIdentifierNotFound = 0x92,
+    ReceiveMaximumExceeded = 0x93,
+    TopicAliasInvalid = 0x94,
+    PacketTooLarge = 0x95,
+    MessageRateTooHigh = 0x96,
+    QuotaExceeded = 0x97,
+    AdministrativeAction = 0x98,
+    PayloadFormatInvalid = 0x99,
+    RetainNotSupported = 0x9A,
+    QoSNotSupported = 0x9B,
+    UseAnotherServer = 0x9C,
+    ServerMoved = 0x9D,
+    SharedSubscriptionsNotSupported = 0x9E,
+    ConnectionRateExceeded = 0x9F,
+    MaximumConnectTime = 0xA0,
+    SubscriptionIdentifiersNotSupported = 0xA1,
+    WildcardSubscriptionsNotSupported = 0xA2,
+}
+
+impl ReasonCode {
+    /// Whether the code reports a failure, i.e. it is 0x80 or greater
+    pub fn is_error(self) -> bool {
+        self.to_u8() >= 0x80
+    }
+}
+
+/// Maps a MQTT 3.1.1 `CONNACK` return code to the MQTT 5 reason code with the same meaning
+///
+/// Reserved return codes have no equivalent and become `ReasonCode::UnspecifiedError`.
+impl From<ConnectReturnCode> for ReasonCode {
+    fn from(code: ConnectReturnCode) -> ReasonCode {
+        match code {
+            ConnectReturnCode::ConnectionAccepted => ReasonCode::Success,
+            ConnectReturnCode::UnacceptableProtocolVersion => ReasonCode::UnsupportedProtocolVersion,
+            ConnectReturnCode::IdentifierRejected => ReasonCode::ClientIdentifierNotValid,
+            ConnectReturnCode::ServiceUnavailable => ReasonCode::ServerUnavailable,
+            ConnectReturnCode::BadUserNameOrPassword => ReasonCode::BadUserNameOrPassword,
+            ConnectReturnCode::NotAuthorized => ReasonCode::NotAuthorized,
+            ConnectReturnCode::Reserved(_) => ReasonCode::UnspecifiedError,
+        }
+    }
+}
+
+/// Maps a MQTT 5 reason code back to the MQTT 3.1.1 `CONNACK` return code with the same meaning
+impl TryFrom<ReasonCode> for ConnectReturnCode {
+    type Error = NoConnectReturnCode;
+
+    fn try_from(code: ReasonCode) -> Result<ConnectReturnCode, NoConnectReturnCode> {
+        match code {
+            ReasonCode::Success => Ok(ConnectReturnCode::ConnectionAccepted),
+            ReasonCode::UnsupportedProtocolVersion => Ok(ConnectReturnCode::UnacceptableProtocolVersion),
+            ReasonCode::ClientIdentifierNotValid => Ok(ConnectReturnCode::IdentifierRejected),
+            ReasonCode::ServerUnavailable => Ok(ConnectReturnCode::ServiceUnavailable),
+            ReasonCode::BadUserNameOrPassword => Ok(ConnectReturnCode::BadUserNameOrPassword),
+            ReasonCode::NotAuthorized => Ok(ConnectReturnCode::NotAuthorized),
+            code => Err(NoConnectReturnCode(code)),
+        }
+    }
+}
+
+/// Reason code without an equivalent MQTT 3.1.1 `CONNACK` return code
+#[derive(Debug, Eq, PartialEq, Copy, Clone, thiserror::Error)]
+#[error("no CONNACK return code for reason code {0:?}")]
+pub struct NoConnectReturnCode(pub ReasonCode);
+
+impl Encodable for ReasonCode {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<(), io::Error> {
+        writer.write_u8(self.to_u8())
+    }
+
+    fn encoded_length(&self) -> u32 {
+        1
+    }
+}
+
+impl Decodable for ReasonCode {
+    type Error = VariableHeaderError;
+    type Cond = ();
+
+    fn decode_with<R: Read>(reader: &mut R, _rest: ()) -> Result<ReasonCode, VariableHeaderError> {
+        reader.read_u8().map(ReasonCode::from_u8).map_err(From::from)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reason_code_from_connect_return_code() {
+        for code in 0..=5 {
+            let ret_code = ConnectReturnCode::from_u8(code);
+            let reason_code = ReasonCode::from(ret_code);
+            assert_eq!(reason_code.is_error(), code != 0);
+            assert_eq!(ConnectReturnCode::try_from(reason_code).unwrap(), ret_code);
+        }
+        assert_eq!(ReasonCode::from(ConnectReturnCode::IdentifierRejected).to_u8(), 0x85);
+        assert_eq!(
+            ReasonCode::from(ConnectReturnCode::Reserved(6)),
+            ReasonCode::UnspecifiedError
+        );
+        assert_eq!(
+            ConnectReturnCode::try_from(ReasonCode::Banned),
+            Err(NoConnectReturnCode(ReasonCode::Banned))
+        );
+    }
+
+    #[test]
+    fn test_reason_code_u8() {
+        for code in 0..=u8::MAX {
+            assert_eq!(ReasonCode::from_u8(code).to_u8(), code);
+        }
+        assert_eq!(ReasonCode::from_u8(0x03), ReasonCode::Reserved(0x03));
+        assert_eq!(ReasonCode::from_u8(0xA2), ReasonCode::WildcardSubscriptionsNotSupported);
+    }
+}
@@ -1,11 +1,12 @@
-use std::io::{self, Read, Write};
+use alloc::string::String;
 
 use crate::control::variable_header::VariableHeaderError;
+use crate::io::{self, Read, Write};
 use crate::topic_name::TopicName;
 use crate::{Decodable, Encodable};
 
 /// Topic name wrapper
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub struct TopicNameHeader(TopicName);
 
 impl TopicNameHeader {
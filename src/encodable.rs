@@ -1,12 +1,51 @@
 //! Encodable traits
 
-use std::convert::Infallible;
-use std::error::Error;
+use alloc::{string::String, vec::Vec};
+use core::cmp;
+use core::convert::{Infallible, TryFrom};
+#[cfg(not(feature = "std"))]
+use core::fmt::Debug;
+use core::marker::Sized;
+
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::io::{self, Read, Write};
+
+/// Integer reading for `io::Read`, as `byteorder::ReadBytesExt` is only available with `std`
+pub(crate) trait ReadBytesExt: Read {
+    #[inline]
+    fn read_u8(&mut self) -> io::Result<u8> {
+        let mut buf = [0; 1];
+        self.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    #[inline]
+    fn read_u16<T: ByteOrder>(&mut self) -> io::Result<u16> {
+        let mut buf = [0; 2];
+        self.read_exact(&mut buf)?;
+        Ok(T::read_u16(&buf))
+    }
+}
+
+impl<R: Read + ?Sized> ReadBytesExt for R {}
+
+/// Integer writing for `io::Write`, as `byteorder::WriteBytesExt` is only available with `std`
+pub(crate) trait WriteBytesExt: Write {
+    #[inline]
+    fn write_u8(&mut self, n: u8) -> io::Result<()> {
+        self.write_all(&[n])
+    }
 
-use std::io::{self, Read, Write};
-use std::marker::Sized;
+    #[inline]
+    fn write_u16<T: ByteOrder>(&mut self, n: u16) -> io::Result<()> {
+        let mut buf = [0; 2];
+        T::write_u16(&mut buf, n);
+        self.write_all(&buf)
+    }
+}
 
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+impl<W: Write + ?Sized> WriteBytesExt for W {}
 
 /// Methods for encoding an Object to bytes according to MQTT specification
 pub trait Encodable {
@@ -40,7 +79,10 @@ impl<T: Encodable> Encodable for Option<T> {
 
 /// Methods for decoding bytes to an Object according to MQTT specification
 pub trait Decodable: Sized {
-    type Error: Error;
+    #[cfg(feature = "std")]
+    type Error: std::error::Error;
+    #[cfg(not(feature = "std"))]
+    type Error: Debug;
     type Cond;
 
     /// Decodes object from reader
@@ -55,27 +97,27 @@ pub trait Decodable: Sized {
     fn decode_with<R: Read>(reader: &mut R, cond: Self::Cond) -> Result<Self, Self::Error>;
 }
 
-impl<'a> Encodable for &'a str {
+impl Encodable for &str {
     fn encode<W: Write>(&self, writer: &mut W) -> Result<(), io::Error> {
-        assert!(self.as_bytes().len() <= u16::max_value() as usize);
+        assert!(self.len() <= u16::MAX as usize);
 
         writer
-            .write_u16::<BigEndian>(self.as_bytes().len() as u16)
+            .write_u16::<BigEndian>(self.len() as u16)
             .and_then(|_| writer.write_all(self.as_bytes()))
     }
 
     fn encoded_length(&self) -> u32 {
-        2 + self.as_bytes().len() as u32
+        2 + self.len() as u32
     }
 }
 
-impl<'a> Encodable for &'a [u8] {
+impl Encodable for &[u8] {
     fn encode<W: Write>(&self, writer: &mut W) -> Result<(), io::Error> {
         writer.write_all(self)
     }
 
     fn encoded_length(&self) -> u32 {
-        self.len() as u32
+        u32::try_from(self.len()).unwrap_or(u32::MAX)
     }
 }
 
@@ -100,6 +142,9 @@ impl Decodable for String {
     }
 }
 
+/// Upper bound of the buffer allocated upfront when decoding a `Vec<u8>` of known length
+const MAX_PREALLOCATE_LENGTH: usize = 8 * 1024;
+
 impl Encodable for Vec<u8> {
     fn encode<W: Write>(&self, writer: &mut W) -> Result<(), io::Error> {
         (&self[..]).encode(writer)
@@ -117,7 +162,8 @@ impl Decodable for Vec<u8> {
     fn decode_with<R: Read>(reader: &mut R, length: Option<u32>) -> Result<Vec<u8>, io::Error> {
         match length {
             Some(length) => {
-                let mut buf = Vec::with_capacity(length as usize);
+                // `length` usually comes from the peer, so the buffer grows as data actually arrives
+                let mut buf = Vec::with_capacity(cmp::min(length as usize, MAX_PREALLOCATE_LENGTH));
                 reader.take(length.into()).read_to_end(&mut buf)?;
                 Ok(buf)
             }
@@ -150,12 +196,13 @@ impl Decodable for () {
 }
 
 /// Bytes that encoded with length
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VarBytes(pub Vec<u8>);
 
 impl Encodable for VarBytes {
     fn encode<W: Write>(&self, writer: &mut W) -> Result<(), io::Error> {
-        assert!(self.0.len() <= u16::max_value() as usize);
+        assert!(self.0.len() <= u16::MAX as usize);
         let len = self.0.len() as u16;
         writer.write_u16::<BigEndian>(len)?;
         writer.write_all(&self.0)?;
@@ -178,7 +225,7 @@ impl Decodable for VarBytes {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
 
@@ -201,4 +248,14 @@ mod test {
 
         assert_eq!(decoded, bytes);
     }
+
+    #[test]
+    fn vec_decode_bounded_preallocation() {
+        // Claims 200MB but only 3 bytes are available
+        let mut reader = Cursor::new(&[1, 2, 3][..]);
+        let decoded = Vec::<u8>::decode_with(&mut reader, Some(200 * 1024 * 1024)).unwrap();
+
+        assert_eq!(decoded, [1, 2, 3]);
+        assert!(decoded.capacity() <= MAX_PREALLOCATE_LENGTH);
+    }
 }
@@ -0,0 +1,248 @@
+//! Neutral packet representations for bridging with other MQTT libraries
+//!
+//! Types in this module are plain data with public fields, modelled after the packets of
+//! [`mqttbytes`](https://docs.rs/mqttbytes), so converting to or from another crate only needs
+//! a field-by-field copy of these structs.
+//!
+//! This requires mqtt-rs to be built with `feature = "interop"`
+
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::convert::TryFrom;
+
+use crate::packet::publish::PublishPacketError;
+use crate::packet::{PublishPacket, QoSWithPacketIdentifier, SubscribePacket};
+use crate::topic_filter::{TopicFilter, TopicFilterError};
+use crate::topic_name::{TopicName, TopicNameError};
+use crate::QualityOfService;
+
+/// Neutral form of a `PUBLISH` packet
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Publish {
+    pub topic: String,
+    pub qos: QualityOfService,
+    /// Packet identifier, `0` for QoS 0
+    pub pkid: u16,
+    pub payload: Vec<u8>,
+    pub retain: bool,
+    pub dup: bool,
+}
+
+impl From<PublishPacket> for Publish {
+    fn from(packet: PublishPacket) -> Publish {
+        let (qos, pkid) = packet.qos().split();
+        Publish {
+            topic: packet.topic_name().to_owned(),
+            qos,
+            pkid: pkid.unwrap_or(0),
+            retain: packet.retain(),
+            dup: packet.dup(),
+            payload: packet.payload().to_vec(),
+        }
+    }
+}
+
+impl TryFrom<Publish> for PublishPacket {
+    type Error = PublishConversionError;
+
+    /// Fails if the topic name is invalid, or if the flags and packet identifier would make a
+    /// packet that cannot be encoded, e.g. QoS 1 with identifier 0 or QoS 0 with DUP set
+    fn try_from(publish: Publish) -> Result<PublishPacket, PublishConversionError> {
+        let topic_name = TopicName::new(publish.topic)?;
+        let mut packet = PublishPacket::new(
+            topic_name,
+            QoSWithPacketIdentifier::new(publish.qos, publish.pkid),
+            publish.payload,
+        );
+        packet.set_retain(publish.retain);
+        packet.set_dup(publish.dup);
+        packet.validate()?;
+        Ok(packet)
+    }
+}
+
+/// Errors converting a neutral `Publish` into a `PublishPacket`
+#[derive(Debug, thiserror::Error)]
+pub enum PublishConversionError {
+    #[error(transparent)]
+    TopicNameError(#[from] TopicNameError),
+    #[error(transparent)]
+    ValidationError(#[from] PublishPacketError),
+}
+
+/// Neutral form of a `SUBSCRIBE` packet
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Subscribe {
+    pub pkid: u16,
+    pub filters: Vec<(String, QualityOfService)>,
+}
+
+impl From<SubscribePacket> for Subscribe {
+    fn from(packet: SubscribePacket) -> Subscribe {
+        Subscribe {
+            pkid: packet.packet_identifier(),
+            filters: packet
+                .subscribes()
+                .iter()
+                .map(|(filter, qos)| (filter.to_string(), *qos))
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<Subscribe> for SubscribePacket {
+    type Error = SubscribeConversionError;
+
+    /// Fails if a topic filter is invalid, or if there is no filter at all [MQTT-3.8.3-3]
+    fn try_from(subscribe: Subscribe) -> Result<SubscribePacket, SubscribeConversionError> {
+        if subscribe.filters.is_empty() {
+            return Err(SubscribeConversionError::EmptySubscription);
+        }
+        let subscribes = subscribe
+            .filters
+            .into_iter()
+            .map(|(filter, qos)| TopicFilter::new(filter).map(|filter| (filter, qos)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(SubscribePacket::new(subscribe.pkid, subscribes))
+    }
+}
+
+/// Errors converting a neutral `Subscribe` into a `SubscribePacket`
+#[derive(Debug, thiserror::Error)]
+pub enum SubscribeConversionError {
+    #[error(transparent)]
+    TopicFilterError(#[from] TopicFilterError),
+    #[error("no topic filter in subscription")]
+    EmptySubscription,
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_publish_round_trip() {
+        let mut packet = PublishPacket::new(
+            TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level1(10),
+            b"Hello world!".to_vec(),
+        );
+        packet.set_retain(true);
+        packet.set_dup(true);
+
+        let publish = Publish::from(packet.clone());
+        assert_eq!(
+            publish,
+            Publish {
+                topic: "a/b".to_owned(),
+                qos: QualityOfService::Level1,
+                pkid: 10,
+                payload: b"Hello world!".to_vec(),
+                retain: true,
+                dup: true,
+            }
+        );
+        assert_eq!(PublishPacket::try_from(publish).unwrap(), packet);
+
+        let packet = PublishPacket::new(
+            TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level0,
+            Vec::new(),
+        );
+        let publish = Publish::from(packet.clone());
+        assert_eq!(publish.pkid, 0);
+        assert_eq!(PublishPacket::try_from(publish).unwrap(), packet);
+    }
+
+    #[test]
+    fn test_publish_invalid_topic() {
+        let publish = Publish {
+            topic: "a/#".to_owned(),
+            qos: QualityOfService::Level0,
+            pkid: 0,
+            payload: Vec::new(),
+            retain: false,
+            dup: false,
+        };
+        assert!(matches!(
+            PublishPacket::try_from(publish),
+            Err(PublishConversionError::TopicNameError(..))
+        ));
+    }
+
+    #[test]
+    fn test_publish_invalid_flags() {
+        let publish = Publish {
+            topic: "a/b".to_owned(),
+            qos: QualityOfService::Level1,
+            pkid: 0,
+            payload: Vec::new(),
+            retain: false,
+            dup: false,
+        };
+        assert!(matches!(
+            PublishPacket::try_from(publish.clone()),
+            Err(PublishConversionError::ValidationError(
+                PublishPacketError::ZeroPacketIdentifier(..)
+            ))
+        ));
+
+        let publish = Publish {
+            qos: QualityOfService::Level0,
+            dup: true,
+            ..publish
+        };
+        assert!(matches!(
+            PublishPacket::try_from(publish),
+            Err(PublishConversionError::ValidationError(
+                PublishPacketError::InvalidDupForQoS0
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_subscribe_empty() {
+        let subscribe = Subscribe {
+            pkid: 5,
+            filters: Vec::new(),
+        };
+        assert!(matches!(
+            SubscribePacket::try_from(subscribe),
+            Err(SubscribeConversionError::EmptySubscription)
+        ));
+
+        let subscribe = Subscribe {
+            pkid: 5,
+            filters: vec![("a/#/b".to_owned(), QualityOfService::Level0)],
+        };
+        assert!(matches!(
+            SubscribePacket::try_from(subscribe),
+            Err(SubscribeConversionError::TopicFilterError(..))
+        ));
+    }
+
+    #[test]
+    fn test_subscribe_round_trip() {
+        let packet = SubscribePacket::new(
+            5,
+            vec![
+                (TopicFilter::new("a/+").unwrap(), QualityOfService::Level0),
+                (TopicFilter::new("b/#").unwrap(), QualityOfService::Level2),
+            ],
+        );
+
+        let subscribe = Subscribe::from(packet.clone());
+        assert_eq!(subscribe.pkid, 5);
+        assert_eq!(
+            subscribe.filters,
+            vec![
+                ("a/+".to_owned(), QualityOfService::Level0),
+                ("b/#".to_owned(), QualityOfService::Level2),
+            ]
+        );
+        assert_eq!(SubscribePacket::try_from(subscribe).unwrap(), packet);
+    }
+}
@@ -0,0 +1,387 @@
+//! The subset of `std::io` used by this crate, for `no_std` builds
+//!
+//! Readers and writers come from `embedded-io`: every `embedded_io::Read`, `embedded_io::BufRead`
+//! and `embedded_io::Write` implements the [`Read`], [`BufRead`] and [`Write`] traits of this
+//! module, including `&[u8]`, `&mut [u8]` and `Vec<u8>`. Their errors are converted to [`Error`]
+//! by their `embedded_io::ErrorKind`.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::{cmp, fmt};
+
+/// A specialized `Result` type for I/O operations, as `std::io::Result`
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// A list specifying general categories of I/O error, as `std::io::ErrorKind`
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    NotFound,
+    PermissionDenied,
+    ConnectionRefused,
+    ConnectionReset,
+    ConnectionAborted,
+    NotConnected,
+    AddrInUse,
+    AddrNotAvailable,
+    BrokenPipe,
+    AlreadyExists,
+    InvalidInput,
+    InvalidData,
+    TimedOut,
+    WriteZero,
+    Interrupted,
+    Unsupported,
+    UnexpectedEof,
+    OutOfMemory,
+    Other,
+}
+
+impl From<embedded_io::ErrorKind> for ErrorKind {
+    fn from(kind: embedded_io::ErrorKind) -> ErrorKind {
+        use embedded_io::ErrorKind as E;
+
+        match kind {
+            E::NotFound => ErrorKind::NotFound,
+            E::PermissionDenied => ErrorKind::PermissionDenied,
+            E::ConnectionRefused => ErrorKind::ConnectionRefused,
+            E::ConnectionReset => ErrorKind::ConnectionReset,
+            E::ConnectionAborted => ErrorKind::ConnectionAborted,
+            E::NotConnected => ErrorKind::NotConnected,
+            E::AddrInUse => ErrorKind::AddrInUse,
+            E::AddrNotAvailable => ErrorKind::AddrNotAvailable,
+            E::BrokenPipe => ErrorKind::BrokenPipe,
+            E::AlreadyExists => ErrorKind::AlreadyExists,
+            E::InvalidInput => ErrorKind::InvalidInput,
+            E::InvalidData => ErrorKind::InvalidData,
+            E::TimedOut => ErrorKind::TimedOut,
+            E::WriteZero => ErrorKind::WriteZero,
+            E::Interrupted => ErrorKind::Interrupted,
+            E::Unsupported => ErrorKind::Unsupported,
+            E::OutOfMemory => ErrorKind::OutOfMemory,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// The error type for I/O operations, as `std::io::Error`
+pub struct Error {
+    kind: ErrorKind,
+    error: Option<Box<dyn core::error::Error + Send + Sync>>,
+}
+
+impl Error {
+    /// Creates an error of `kind` wrapping `error`
+    pub fn new<E>(kind: ErrorKind, error: E) -> Error
+    where
+        E: Into<Box<dyn core::error::Error + Send + Sync>>,
+    {
+        Error {
+            kind,
+            error: Some(error.into()),
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The error wrapped by [`Error::new`]
+    pub fn get_ref(&self) -> Option<&(dyn core::error::Error + Send + Sync + 'static)> {
+        self.error.as_deref()
+    }
+
+    pub fn into_inner(self) -> Option<Box<dyn core::error::Error + Send + Sync>> {
+        self.error
+    }
+
+    fn from_embedded<E: embedded_io::Error>(err: E) -> Error {
+        Error::from(ErrorKind::from(err.kind()))
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Error {
+        Error { kind, error: None }
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.error {
+            Some(ref error) => f
+                .debug_struct("Custom")
+                .field("kind", &self.kind)
+                .field("error", error)
+                .finish(),
+            None => f.debug_tuple("Kind").field(&self.kind).finish(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.error {
+            Some(ref error) => error.fmt(f),
+            None => write!(f, "{:?}", self.kind),
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        self.error.as_ref().and_then(|error| error.source())
+    }
+}
+
+/// Reads bytes from a source, as `std::io::Read`
+pub trait Read {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.read(buf) {
+                Ok(0) => return Err(Error::from(ErrorKind::UnexpectedEof)),
+                Ok(n) => buf = &mut buf[n..],
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads until EOF, returns the number of bytes appended to `buf`
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        let start = buf.len();
+        let mut chunk = [0; 64];
+        loop {
+            match self.read(&mut chunk) {
+                Ok(0) => return Ok(buf.len() - start),
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Reads at most `limit` bytes
+    ///
+    /// Unlike `std::io::Read::take`, the reader is borrowed, so `reader.take(n)` works the same
+    /// on a `&mut R` in both builds.
+    fn take(&mut self, limit: u64) -> Take<'_, Self> {
+        Take { inner: self, limit }
+    }
+}
+
+impl<T: embedded_io::Read + ?Sized> Read for T {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        embedded_io::Read::read(self, buf).map_err(Error::from_embedded)
+    }
+}
+
+/// A buffered reader, as `std::io::BufRead`
+pub trait BufRead: Read {
+    fn fill_buf(&mut self) -> Result<&[u8]>;
+
+    fn consume(&mut self, amt: usize);
+}
+
+impl<T: embedded_io::BufRead + embedded_io::Read + ?Sized> BufRead for T {
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        embedded_io::BufRead::fill_buf(self).map_err(Error::from_embedded)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        embedded_io::BufRead::consume(self, amt)
+    }
+}
+
+/// Writes bytes to a sink, as `std::io::Write`
+pub trait Write {
+    fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+    fn flush(&mut self) -> Result<()>;
+
+    fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf) {
+                Ok(0) => return Err(Error::from(ErrorKind::WriteZero)),
+                Ok(n) => buf = &buf[n..],
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: embedded_io::Write + ?Sized> Write for T {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        embedded_io::Write::write(self, buf).map_err(Error::from_embedded)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        embedded_io::Write::flush(self).map_err(Error::from_embedded)
+    }
+}
+
+/// Reader adapter returned by [`Read::take`]
+#[derive(Debug)]
+pub struct Take<'a, R: ?Sized> {
+    inner: &'a mut R,
+    limit: u64,
+}
+
+impl<R: ?Sized> Take<'_, R> {
+    /// Number of bytes that can still be read
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+}
+
+impl<R: Read + ?Sized> Read for Take<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.limit == 0 {
+            return Ok(0);
+        }
+
+        let max = cmp::min(buf.len() as u64, self.limit) as usize;
+        let n = self.inner.read(&mut buf[..max])?;
+        self.limit -= n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    use crate::packet::{PingreqPacket, PublishPacket, QoSWithPacketIdentifier, VariablePacket};
+    use crate::{Decodable, Encodable, TopicName};
+
+    /// Transport failing with `embedded_io` errors, like a driver on a microcontroller
+    struct Transport {
+        data: &'static [u8],
+        errors: Vec<embedded_io::ErrorKind>,
+    }
+
+    impl embedded_io::ErrorType for Transport {
+        type Error = embedded_io::ErrorKind;
+    }
+
+    impl embedded_io::Read for Transport {
+        fn read(&mut self, buf: &mut [u8]) -> core::result::Result<usize, embedded_io::ErrorKind> {
+            if let Some(err) = self.errors.pop() {
+                return Err(err);
+            }
+            // One byte at a time, to exercise the retry loops
+            match self.data.split_first() {
+                Some((&byte, rest)) if !buf.is_empty() => {
+                    buf[0] = byte;
+                    self.data = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_exact() {
+        let mut rdr = &b"abcdef"[..];
+        let mut buf = [0; 4];
+        rdr.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"abcd");
+        assert_eq!(rdr.read_exact(&mut buf).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+
+        let mut rdr = Transport {
+            data: b"abcd",
+            errors: vec![embedded_io::ErrorKind::Interrupted],
+        };
+        rdr.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"abcd");
+
+        let mut rdr = Transport {
+            data: b"abcd",
+            errors: vec![embedded_io::ErrorKind::ConnectionReset],
+        };
+        assert_eq!(rdr.read_exact(&mut buf).unwrap_err().kind(), ErrorKind::ConnectionReset);
+    }
+
+    #[test]
+    fn test_take() {
+        let mut rdr = &b"abcdef"[..];
+        let mut take = rdr.take(4);
+        let mut buf = Vec::new();
+        assert_eq!(take.read_to_end(&mut buf).unwrap(), 4);
+        assert_eq!(take.limit(), 0);
+        assert_eq!(buf, b"abcd");
+        assert_eq!(rdr, b"ef");
+    }
+
+    #[test]
+    fn test_buf_read() {
+        let mut rdr = &b"abc"[..];
+        assert_eq!(rdr.fill_buf().unwrap(), b"abc");
+        rdr.consume(2);
+        assert_eq!(rdr, b"c");
+    }
+
+    #[test]
+    fn test_write() {
+        let mut buf = [0; 4];
+        let mut wr = &mut buf[..];
+        wr.write_all(b"ab").unwrap();
+        assert_eq!(wr.write_all(b"cde").unwrap_err().kind(), ErrorKind::WriteZero);
+        assert_eq!(&buf, b"abcd");
+
+        let mut wr = Vec::new();
+        wr.write_all(b"abc").unwrap();
+        wr.flush().unwrap();
+        assert_eq!(wr, b"abc");
+    }
+
+    #[test]
+    fn test_error() {
+        let err = Error::new(ErrorKind::InvalidData, "bad data");
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "bad data");
+        assert!(err.get_ref().is_some());
+
+        let err = Error::from(ErrorKind::UnexpectedEof);
+        assert_eq!(err.to_string(), "UnexpectedEof");
+        assert!(err.get_ref().is_none());
+    }
+
+    #[test]
+    fn test_packet_round_trip() {
+        let packet = VariablePacket::new(PublishPacket::new(
+            TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level1(10),
+            "payload",
+        ));
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+
+        let mut rdr = &buf[..];
+        assert_eq!(VariablePacket::decode(&mut rdr).unwrap(), packet);
+        assert!(rdr.is_empty());
+        assert!(VariablePacket::decode(&mut &buf[..5]).is_err());
+
+        let mut small = [0; 4];
+        let err = packet.encode(&mut &mut small[..]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WriteZero);
+
+        let mut rdr = Transport {
+            data: b"\xC0\x00",
+            errors: vec![embedded_io::ErrorKind::Interrupted],
+        };
+        assert_eq!(
+            VariablePacket::decode(&mut rdr).unwrap(),
+            VariablePacket::new(PingreqPacket::new())
+        );
+    }
+}
@@ -5,8 +5,6 @@
 //! ## Usage
 //!
 //! ```rust
-//! use std::io::Cursor;
-//!
 //! use mqtt::{Encodable, Decodable};
 //! use mqtt::packet::{VariablePacket, PublishPacket, QoSWithPacketIdentifier};
 //! use mqtt::TopicName;
@@ -22,17 +20,36 @@
 //! println!("Encoded: {:?}", buf);
 //!
 //! // Decode it with known type
-//! let mut dec_buf = Cursor::new(&buf[..]);
+//! let mut dec_buf = &buf[..];
 //! let decoded = PublishPacket::decode(&mut dec_buf).unwrap();
 //! println!("Decoded: {:?}", decoded);
 //! assert_eq!(packet, decoded);
 //!
 //! // Auto decode by the fixed header
-//! let mut dec_buf = Cursor::new(&buf[..]);
+//! let mut dec_buf = &buf[..];
 //! let auto_decode = VariablePacket::decode(&mut dec_buf).unwrap();
 //! println!("Variable packet decode: {:?}", auto_decode);
 //! assert_eq!(VariablePacket::PublishPacket(packet), auto_decode);
 //! ```
+//!
+//! ## `no_std`
+//!
+//! The default `std` feature can be disabled to build on targets with only `alloc`. Packets are
+//! then read from and written to `embedded-io` readers and writers, through the [`io`] module
+//! which replaces `std::io`. Decoding errors are then only required to implement `Debug` instead
+//! of `std::error::Error`. The error types of this crate implement `core::error::Error` in that
+//! case, which requires Rust 1.81.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+/// I/O traits and errors used for encoding and decoding
+#[cfg(feature = "std")]
+pub use std::io;
+
+#[cfg(not(feature = "std"))]
+pub mod io;
 
 pub use self::encodable::{Decodable, Encodable};
 pub use self::qos::QualityOfService;
@@ -41,6 +58,8 @@ pub use self::topic_name::{TopicName, TopicNameRef};
 
 pub mod control;
 pub mod encodable;
+#[cfg(feature = "interop")]
+pub mod interop;
 pub mod packet;
 pub mod qos;
 pub mod topic_filter;
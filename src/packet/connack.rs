@@ -1,21 +1,28 @@
 //! CONNACK
 
-use std::io::Read;
+use core::fmt;
 
 use crate::control::variable_header::{ConnackFlags, ConnectReturnCode};
 use crate::control::{ControlType, FixedHeader, PacketType};
+use crate::io::Read;
 use crate::packet::{DecodablePacket, PacketError};
 use crate::Decodable;
 
 /// `CONNACK` packet
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(remote = "Self")
+)]
 pub struct ConnackPacket {
     fixed_header: FixedHeader,
     flags: ConnackFlags,
     ret_code: ConnectReturnCode,
 }
 
-encodable_packet!(ConnackPacket(flags, ret_code));
+encodable_packet!(ConnackPacket(flags, ret_code), validate = validate);
+serde_packet!(ConnackPacket, ConnectAcknowledgement);
 
 impl ConnackPacket {
     pub fn new(session_present: bool, ret_code: ConnectReturnCode) -> ConnackPacket {
@@ -26,17 +33,63 @@ impl ConnackPacket {
         }
     }
 
+    /// Like `new`, but rejects the session present flag with a return code other than
+    /// `ConnectionAccepted` [MQTT-3.2.2-4]
+    pub fn try_new(
+        session_present: bool,
+        ret_code: ConnectReturnCode,
+    ) -> Result<ConnackPacket, SessionPresentOnFailure> {
+        let packet = ConnackPacket::new(session_present, ret_code);
+        packet.validate()?;
+        Ok(packet)
+    }
+
+    /// Checks that the session present flag is only set when the connection is accepted
+    /// [MQTT-3.2.2-4], encoding the packet fails otherwise
+    pub fn validate(&self) -> Result<(), SessionPresentOnFailure> {
+        if self.flags.session_present && self.ret_code != ConnectReturnCode::ConnectionAccepted {
+            return Err(SessionPresentOnFailure(self.ret_code));
+        }
+        Ok(())
+    }
+
+    /// Creates a `CONNACK` packet with a return code that may not be defined by the specification,
+    /// e.g. for testing how clients handle reserved codes
+    pub fn with_raw_return_code(session_present: bool, code: u8) -> ConnackPacket {
+        ConnackPacket::new(session_present, ConnectReturnCode::from_u8(code))
+    }
+
     pub fn connack_flags(&self) -> ConnackFlags {
         self.flags
     }
 
+    /// Whether the server resumed a session for the client, from the connect acknowledge flags
+    pub fn session_present(&self) -> bool {
+        self.flags.session_present
+    }
+
     pub fn connect_return_code(&self) -> ConnectReturnCode {
         self.ret_code
     }
 }
 
+impl fmt::Display for ConnackPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "CONNACK session_present={} code={:?}",
+            self.flags.session_present, self.ret_code
+        )
+    }
+}
+
+/// Session present flag set on a `CONNACK` that refuses the connection
+#[derive(Debug, Eq, PartialEq, Copy, Clone, thiserror::Error)]
+#[error("session present flag is set with return code {0:?}")]
+pub struct SessionPresentOnFailure(pub ConnectReturnCode);
+
 impl DecodablePacket for ConnackPacket {
-    type DecodePacketError = std::convert::Infallible;
+    type DecodePacketError = core::convert::Infallible;
 
     fn decode_packet<R: Read>(reader: &mut R, fixed_header: FixedHeader) -> Result<Self, PacketError<Self>> {
         let flags: ConnackFlags = Decodable::decode(reader)?;
@@ -50,11 +103,11 @@ impl DecodablePacket for ConnackPacket {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
 
-    use std::io::Cursor;
+    use std::io::{self, Cursor};
 
     use crate::control::variable_header::ConnectReturnCode;
     use crate::{Decodable, Encodable};
@@ -71,4 +124,34 @@ mod test {
 
         assert_eq!(packet, decoded);
     }
+
+    #[test]
+    pub fn test_connack_packet_session_present() {
+        let packet = ConnackPacket::try_new(true, ConnectReturnCode::ConnectionAccepted).unwrap();
+        assert!(packet.connack_flags().session_present);
+        assert!(packet.session_present());
+        assert!(!ConnackPacket::new(false, ConnectReturnCode::ConnectionAccepted).session_present());
+
+        assert_eq!(
+            ConnackPacket::try_new(true, ConnectReturnCode::NotAuthorized),
+            Err(SessionPresentOnFailure(ConnectReturnCode::NotAuthorized))
+        );
+        let packet = ConnackPacket::new(true, ConnectReturnCode::NotAuthorized);
+        let err = packet.encode(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    pub fn test_connack_packet_reserved_return_code() {
+        let mut decode_buf = Cursor::new(&b"\x20\x02\x00\x10"[..]);
+        let decoded = ConnackPacket::decode(&mut decode_buf).unwrap();
+        assert_eq!(decoded.connect_return_code(), ConnectReturnCode::Reserved(0x10));
+
+        let packet = ConnackPacket::with_raw_return_code(false, 0x10);
+        assert_eq!(packet, decoded);
+
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+        assert_eq!(&buf[..], b"\x20\x02\x00\x10");
+    }
 }
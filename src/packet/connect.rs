@@ -1,17 +1,33 @@
 //! CONNECT
 
-use std::io::{self, Read, Write};
+use alloc::{string::String, vec, vec::Vec};
+use core::fmt;
 
-use crate::control::variable_header::protocol_level::SPEC_3_1_1;
+use byteorder::BigEndian;
+
+use crate::control::variable_header::protocol_level::{SPEC_3_1_0, SPEC_3_1_1};
 use crate::control::variable_header::{ConnectFlags, KeepAlive, ProtocolLevel, ProtocolName, VariableHeaderError};
 use crate::control::{ControlType, FixedHeader, PacketType};
-use crate::encodable::VarBytes;
-use crate::packet::{DecodablePacket, PacketError};
-use crate::topic_name::{TopicName, TopicNameDecodeError, TopicNameError};
-use crate::{Decodable, Encodable};
+use crate::encodable::{VarBytes, WriteBytesExt};
+use crate::io::{self, Read, Write};
+use crate::packet::{DecodablePacket, EncodablePacket, PacketError};
+use crate::topic_name::{TopicName, TopicNameDecodeError, TopicNameError, TopicNameRef};
+use crate::{Decodable, Encodable, QualityOfService};
+
+const PROTOCOL_NAME: &str = "MQTT";
+const PROTOCOL_NAME_V31: &str = "MQIsdp";
+
+/// Maximum length in characters of client identifiers in MQTT v3.1, which is also the length every
+/// MQTT v3.1.1 server must accept [MQTT-3.1.3-5]
+pub const V31_MAX_CLIENT_IDENTIFIER_LENGTH: usize = 23;
 
 /// `CONNECT` packet
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(remote = "Self")
+)]
 pub struct ConnectPacket {
     fixed_header: FixedHeader,
     protocol_name: ProtocolName,
@@ -23,14 +39,54 @@ pub struct ConnectPacket {
     payload: ConnectPacketPayload,
 }
 
-encodable_packet!(ConnectPacket(protocol_name, protocol_level, flags, keep_alive, payload));
+serde_packet!(ConnectPacket, Connect);
+
+impl EncodablePacket for ConnectPacket {
+    fn fixed_header(&self) -> &FixedHeader {
+        &self.fixed_header
+    }
+
+    fn encode_packet<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.validate()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        self.protocol_name.encode(writer)?;
+        self.protocol_level.encode(writer)?;
+        self.flags.encode(writer)?;
+        self.keep_alive.encode(writer)?;
+        self.payload.encode(writer)
+    }
+
+    fn encoded_packet_length(&self) -> u32 {
+        self.protocol_name.encoded_length()
+            + self.protocol_level.encoded_length()
+            + self.flags.encoded_length()
+            + self.keep_alive.encoded_length()
+            + self.payload.encoded_length()
+    }
+}
 
 impl ConnectPacket {
     pub fn new<C>(client_identifier: C) -> ConnectPacket
     where
         C: Into<String>,
     {
-        ConnectPacket::with_level("MQTT", client_identifier, SPEC_3_1_1).expect("SPEC_3_1_1 should always be valid")
+        ConnectPacket::with_level(PROTOCOL_NAME, client_identifier, SPEC_3_1_1)
+            .expect("SPEC_3_1_1 should always be valid")
+    }
+
+    /// Creates a `CONNECT` packet of MQTT v3.1, for legacy brokers
+    ///
+    /// MQTT v3.1 requires client identifiers to be 1 to 23 characters long, which is only checked
+    /// when the packet is validated or encoded. Check it beforehand with
+    /// [`client_identifier_fits_v31`](ConnectPacket::client_identifier_fits_v31) if the identifier
+    /// may be longer.
+    pub fn with_v31<C>(client_identifier: C) -> ConnectPacket
+    where
+        C: Into<String>,
+    {
+        ConnectPacket::with_level(PROTOCOL_NAME_V31, client_identifier, SPEC_3_1_0)
+            .expect("SPEC_3_1_0 should always be valid")
     }
 
     pub fn with_level<P, C>(protoname: P, client_identifier: C, level: u8) -> Result<ConnectPacket, VariableHeaderError>
@@ -53,6 +109,84 @@ impl ConnectPacket {
         Ok(pk)
     }
 
+    fn fix_header_remaining_len(&mut self) {
+        self.fixed_header.remaining_length = self.encoded_packet_length();
+    }
+
+    /// Names and lengths of the encoded fields after fixed header
+    pub(crate) fn encoded_fields(&self) -> Vec<(&'static str, u32)> {
+        vec![
+            ("protocol_name", self.protocol_name.encoded_length()),
+            ("protocol_level", self.protocol_level.encoded_length()),
+            ("flags", self.flags.encoded_length()),
+            ("keep_alive", self.keep_alive.encoded_length()),
+            ("payload", self.payload.encoded_length()),
+        ]
+    }
+
+    /// Checks the packet against rules that the setters cannot enforce on their own, for its
+    /// protocol level
+    ///
+    /// Will QoS and Will Retain must be 0 without a Will Message [MQTT-3.1.2-13] [MQTT-3.1.2-15].
+    /// MQTT v3.1 requires a client identifier of 1 to [`V31_MAX_CLIENT_IDENTIFIER_LENGTH`]
+    /// characters. In MQTT v3.1.1, the password flag must not be set without the user name flag
+    /// [MQTT-3.1.2-22], and the client identifier may only be empty with clean session
+    /// [MQTT-3.1.3-7]. MQTT 5 lifts both of these restrictions.
+    pub fn validate(&self) -> Result<(), ConnectPacketError> {
+        if self.flags.will_qos > 2 {
+            return Err(VariableHeaderError::InvalidWillQoS(self.flags.will_qos).into());
+        }
+        if !self.flags.will_flag && self.flags.will_qos != 0 {
+            return Err(VariableHeaderError::WillQoSWithoutWillFlag(self.flags.will_qos).into());
+        }
+        if !self.flags.will_flag && self.flags.will_retain {
+            return Err(ConnectPacketError::WillRetainWithoutWillFlag);
+        }
+
+        match self.protocol_level {
+            ProtocolLevel::Version310 => {
+                if !self.client_identifier_fits_v31() {
+                    return Err(ConnectPacketError::InvalidClientIdentifierLength {
+                        length: self.client_identifier().chars().count(),
+                        max: V31_MAX_CLIENT_IDENTIFIER_LENGTH,
+                    });
+                }
+            }
+            ProtocolLevel::Version311 => {
+                if self.flags.password && !self.flags.user_name {
+                    return Err(ConnectPacketError::PasswordWithoutUserName);
+                }
+                if self.payload.client_identifier.is_empty() && !self.flags.clean_session {
+                    return Err(ConnectPacketError::EmptyClientIdentifierWithoutCleanSession);
+                }
+            }
+            ProtocolLevel::Version50 => {}
+        }
+        Ok(())
+    }
+
+    /// Checks that the client identifier is one that every server must accept: 1 to
+    /// [`V31_MAX_CLIENT_IDENTIFIER_LENGTH`] bytes of `[0-9a-zA-Z]` [MQTT-3.1.3-5]
+    ///
+    /// Servers may allow other identifiers, so this is only a pre-check for strict brokers.
+    pub fn validate_client_id_conformant(&self) -> bool {
+        // Only ASCII characters are allowed, so the length in characters is the length in bytes
+        self.client_identifier_fits_v31() && self.client_identifier().bytes().all(|b| b.is_ascii_alphanumeric())
+    }
+
+    /// Checks that the client identifier is accepted by MQTT v3.1, which allows 1 to
+    /// [`V31_MAX_CLIENT_IDENTIFIER_LENGTH`] characters
+    pub fn client_identifier_fits_v31(&self) -> bool {
+        self.client_identifier_fits(V31_MAX_CLIENT_IDENTIFIER_LENGTH)
+    }
+
+    /// Checks that the client identifier is 1 to `max` characters long, for brokers configured with
+    /// their own limit
+    pub fn client_identifier_fits(&self, max: usize) -> bool {
+        let len = self.client_identifier().chars().count();
+        (1..=max).contains(&len)
+    }
+
     pub fn set_keep_alive(&mut self, keep_alive: u16) {
         self.keep_alive = KeepAlive(keep_alive);
     }
@@ -63,14 +197,41 @@ impl ConnectPacket {
         self.fix_header_remaining_len();
     }
 
-    pub fn set_will(&mut self, topic_message: Option<(TopicName, Vec<u8>)>) {
-        self.flags.will_flag = topic_message.is_some();
-
-        self.payload.will = topic_message.map(|(t, m)| (t, VarBytes(m)));
+    /// Sets the Will Message together with its QoS and retain flag, or clears all of them
+    pub fn set_will_message(&mut self, will: Option<Will>) {
+        match will {
+            Some(will) => {
+                self.flags.will_flag = true;
+                self.flags.will_qos = will.qos as u8;
+                self.flags.will_retain = will.retain;
+                self.payload.will = Some((will.topic, VarBytes(will.payload)));
+            }
+            None => {
+                // Will QoS and Will Retain must be 0 if the Will Flag is 0 [MQTT-3.1.2-13] [MQTT-3.1.2-15]
+                self.flags.will_flag = false;
+                self.flags.will_qos = 0;
+                self.flags.will_retain = false;
+                self.payload.will = None;
+            }
+        }
 
         self.fix_header_remaining_len();
     }
 
+    /// Sets the Will Topic and Will Message, keeping the current Will QoS and Will Retain
+    ///
+    /// Prefer `set_will_message`, which sets all properties of the will at once.
+    pub fn set_will(&mut self, topic_message: Option<(TopicName, Vec<u8>)>) {
+        let qos = QualityOfService::from_u8(self.flags.will_qos).unwrap_or(QualityOfService::Level0);
+        let retain = self.flags.will_retain;
+        self.set_will_message(topic_message.map(|(topic, payload)| Will {
+            topic,
+            payload,
+            qos,
+            retain,
+        }));
+    }
+
     pub fn set_password(&mut self, password: Option<String>) {
         self.flags.password = password.is_some();
         self.payload.password = password;
@@ -82,13 +243,47 @@ impl ConnectPacket {
         self.fix_header_remaining_len();
     }
 
-    pub fn set_will_retain(&mut self, will_retain: bool) {
-        self.flags.will_retain = will_retain;
+    /// Sets the Will Retain flag of the current Will Message
+    ///
+    /// Will Retain must be 0 without a Will Message [MQTT-3.1.2-15]. Prefer `set_will_message`, which
+    /// sets all properties of the will at once.
+    pub fn set_will_retain(&mut self, will_retain: bool) -> Result<(), ConnectPacketError> {
+        match self.payload.will.take() {
+            Some((topic, payload)) => {
+                let qos = QualityOfService::from_u8(self.flags.will_qos).unwrap_or(QualityOfService::Level0);
+                self.set_will_message(Some(Will {
+                    topic,
+                    payload: payload.0,
+                    qos,
+                    retain: will_retain,
+                }));
+                Ok(())
+            }
+            None if !will_retain => Ok(()),
+            None => Err(ConnectPacketError::WillRetainWithoutWillFlag),
+        }
     }
 
-    pub fn set_will_qos(&mut self, will_qos: u8) {
-        assert!(will_qos <= 2);
-        self.flags.will_qos = will_qos;
+    /// Sets the Will QoS of the current Will Message
+    ///
+    /// Will QoS must be 0 without a Will Message [MQTT-3.1.2-13]. Prefer `set_will_message`, which sets
+    /// all properties of the will at once.
+    pub fn set_will_qos(&mut self, will_qos: u8) -> Result<(), ConnectPacketError> {
+        let qos = QualityOfService::from_u8(will_qos).ok_or(VariableHeaderError::InvalidWillQoS(will_qos))?;
+        match self.payload.will.take() {
+            Some((topic, payload)) => {
+                let retain = self.flags.will_retain;
+                self.set_will_message(Some(Will {
+                    topic,
+                    payload: payload.0,
+                    qos,
+                    retain,
+                }));
+                Ok(())
+            }
+            None if qos == QualityOfService::Level0 => Ok(()),
+            None => Err(VariableHeaderError::WillQoSWithoutWillFlag(will_qos).into()),
+        }
     }
 
     pub fn set_clean_session(&mut self, clean_session: bool) {
@@ -107,6 +302,16 @@ impl ConnectPacket {
         self.payload.will.as_ref().map(|(topic, msg)| (&topic[..], &*msg.0))
     }
 
+    /// The will with its QoS and retain flag, as set by `set_will_message`
+    pub fn will_message(&self) -> Option<WillRef<'_>> {
+        self.payload.will.as_ref().map(|(topic, msg)| WillRef {
+            topic,
+            payload: &msg.0,
+            qos: QualityOfService::from_u8(self.flags.will_qos).unwrap_or(QualityOfService::Level0),
+            retain: self.flags.will_retain,
+        })
+    }
+
     pub fn will_retain(&self) -> bool {
         self.flags.will_retain
     }
@@ -142,12 +347,43 @@ impl ConnectPacket {
     }
 }
 
+impl fmt::Display for ConnectPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "CONNECT client_id={} protocol={} level={} clean_session={} keep_alive={}",
+            self.client_identifier(),
+            self.protocol_name(),
+            self.protocol_level as u8,
+            self.clean_session(),
+            self.keep_alive()
+        )?;
+        if let Some(user_name) = self.user_name() {
+            write!(f, " user_name={}", user_name)?;
+        }
+        if let Some((topic, _)) = self.will() {
+            write!(
+                f,
+                " will={}:QoS{} retain={}",
+                topic,
+                self.will_qos(),
+                self.will_retain()
+            )?;
+        }
+        Ok(())
+    }
+}
+
 impl DecodablePacket for ConnectPacket {
     type DecodePacketError = ConnectPacketError;
 
     fn decode_packet<R: Read>(reader: &mut R, fixed_header: FixedHeader) -> Result<Self, PacketError<Self>> {
         let protoname: ProtocolName = Decodable::decode(reader)?;
         let protocol_level: ProtocolLevel = Decodable::decode(reader)?;
+        // "MQIsdp" is the name of v3.1 only, and "MQTT" the name of every later version
+        if protoname.0 != protocol_level.protocol_name() {
+            return Err(VariableHeaderError::MismatchedProtocolName(protoname.0, protocol_level).into());
+        }
         let flags: ConnectFlags = Decodable::decode(reader)?;
         let keep_alive: KeepAlive = Decodable::decode(reader)?;
         let payload: ConnectPacketPayload =
@@ -164,8 +400,153 @@ impl DecodablePacket for ConnectPacket {
     }
 }
 
+/// Will Message in `CONNECT` packet, published by the server when the client disconnects unexpectedly
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Will {
+    pub topic: TopicName,
+    pub payload: Vec<u8>,
+    pub qos: QualityOfService,
+    pub retain: bool,
+}
+
+/// `CONNECT` packet that borrows its payload, for encoding without copying
+///
+/// The protocol level is always MQTT v3.1.1.
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub struct ConnectPacketRef<'a> {
+    fixed_header: FixedHeader,
+    flags: ConnectFlags,
+    keep_alive: KeepAlive,
+
+    client_identifier: &'a str,
+    will: Option<(&'a TopicNameRef, &'a [u8])>,
+    user_name: Option<&'a str>,
+    password: Option<&'a str>,
+}
+
+impl<'a> ConnectPacketRef<'a> {
+    pub fn new(client_identifier: &'a str) -> ConnectPacketRef<'a> {
+        let mut pk = ConnectPacketRef {
+            fixed_header: FixedHeader::new(PacketType::with_default(ControlType::Connect), 0),
+            flags: ConnectFlags::empty(),
+            keep_alive: KeepAlive(0),
+            client_identifier,
+            will: None,
+            user_name: None,
+            password: None,
+        };
+        pk.fix_header_remaining_len();
+        pk
+    }
+
+    fn fix_header_remaining_len(&mut self) {
+        self.fixed_header.remaining_length = self.encoded_packet_length();
+    }
+
+    pub fn set_keep_alive(&mut self, keep_alive: u16) {
+        self.keep_alive = KeepAlive(keep_alive);
+    }
+
+    pub fn set_clean_session(&mut self, clean_session: bool) {
+        self.flags.clean_session = clean_session;
+    }
+
+    pub fn set_user_name(&mut self, name: Option<&'a str>) {
+        self.flags.user_name = name.is_some();
+        self.user_name = name;
+        self.fix_header_remaining_len();
+    }
+
+    pub fn set_password(&mut self, password: Option<&'a str>) {
+        self.flags.password = password.is_some();
+        self.password = password;
+        self.fix_header_remaining_len();
+    }
+
+    /// Sets the Will Message together with its QoS and retain flag, or clears all of them
+    pub fn set_will_message(&mut self, will: Option<WillRef<'a>>) {
+        match will {
+            Some(will) => {
+                self.flags.will_flag = true;
+                self.flags.will_qos = will.qos as u8;
+                self.flags.will_retain = will.retain;
+                self.will = Some((will.topic, will.payload));
+            }
+            None => {
+                self.flags.will_flag = false;
+                self.flags.will_qos = 0;
+                self.flags.will_retain = false;
+                self.will = None;
+            }
+        }
+
+        self.fix_header_remaining_len();
+    }
+}
+
+impl EncodablePacket for ConnectPacketRef<'_> {
+    fn fixed_header(&self) -> &FixedHeader {
+        &self.fixed_header
+    }
+
+    fn encode_packet<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        if self.flags.password && !self.flags.user_name {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                ConnectPacketError::PasswordWithoutUserName,
+            ));
+        }
+        if self.client_identifier.is_empty() && !self.flags.clean_session {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                ConnectPacketError::EmptyClientIdentifierWithoutCleanSession,
+            ));
+        }
+
+        PROTOCOL_NAME.encode(writer)?;
+        ProtocolLevel::Version311.encode(writer)?;
+        self.flags.encode(writer)?;
+        self.keep_alive.encode(writer)?;
+
+        self.client_identifier.encode(writer)?;
+        if let Some((will_topic, will_message)) = self.will {
+            will_topic.encode(writer)?;
+            assert!(will_message.len() <= u16::MAX as usize);
+            writer.write_u16::<BigEndian>(will_message.len() as u16)?;
+            will_message.encode(writer)?;
+        }
+        self.user_name.encode(writer)?;
+        self.password.encode(writer)
+    }
+
+    fn encoded_packet_length(&self) -> u32 {
+        PROTOCOL_NAME.encoded_length()
+            + ProtocolLevel::Version311.encoded_length()
+            + self.flags.encoded_length()
+            + self.keep_alive.encoded_length()
+            + self.client_identifier.encoded_length()
+            + self
+                .will
+                .map(|(topic, message)| topic.encoded_length() + 2 + message.encoded_length())
+                .unwrap_or(0)
+            + self.user_name.encoded_length()
+            + self.password.encoded_length()
+    }
+}
+
+/// Borrowed form of [`Will`], for [`ConnectPacketRef`]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub struct WillRef<'a> {
+    pub topic: &'a TopicNameRef,
+    pub payload: &'a [u8],
+    pub qos: QualityOfService,
+    pub retain: bool,
+}
+
 /// Payloads for connect packet
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct ConnectPacketPayload {
     client_identifier: String,
     will: Option<(TopicName, VarBytes)>,
@@ -266,13 +647,24 @@ impl Decodable for ConnectPacketPayload {
 }
 
 #[derive(Debug, thiserror::Error)]
-#[error(transparent)]
 pub enum ConnectPacketError {
+    #[error(transparent)]
     IoError(#[from] io::Error),
+    #[error(transparent)]
     TopicNameError(#[from] TopicNameError),
+    #[error(transparent)]
+    VariableHeaderError(#[from] VariableHeaderError),
+    #[error("will retain flag is set without will flag")]
+    WillRetainWithoutWillFlag,
+    #[error("password flag is set without user name flag")]
+    PasswordWithoutUserName,
+    #[error("client identifier is empty without clean session")]
+    EmptyClientIdentifierWithoutCleanSession,
+    #[error("client identifier of {length} characters, expected 1 to {max}")]
+    InvalidClientIdentifierLength { length: usize, max: usize },
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
 
@@ -302,6 +694,74 @@ mod test {
         assert_eq!(expected, packet);
     }
 
+    #[test]
+    fn test_connect_packet_keep_alive() {
+        let mut packet = ConnectPacket::new("12345".to_owned());
+        assert_eq!(packet.keep_alive(), 0);
+        packet.set_keep_alive(30);
+
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+        assert_eq!(&buf[10..12], b"\x00\x1E");
+
+        let decoded = ConnectPacket::decode(&mut Cursor::new(buf)).unwrap();
+        assert_eq!(decoded.keep_alive(), 30);
+    }
+
+    #[test]
+    fn test_connect_packet_v31() {
+        let packet = ConnectPacket::with_v31("12345");
+        assert_eq!(packet.protocol_name(), "MQIsdp");
+        assert_eq!(packet.protocol_level(), ProtocolLevel::Version310);
+        assert!(packet.client_identifier_fits_v31());
+
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+        assert_eq!(&buf[..], b"\x10\x13\x00\x06MQIsdp\x03\x00\x00\x00\x00\x0512345");
+
+        let decoded = ConnectPacket::decode(&mut Cursor::new(buf)).unwrap();
+        assert_eq!(packet, decoded);
+
+        assert!(!ConnectPacket::with_v31("").client_identifier_fits_v31());
+        assert!(ConnectPacket::with_v31("a".repeat(24)).client_identifier_fits(64));
+        assert!(!ConnectPacket::with_v31("12345").client_identifier_fits(4));
+        let packet = ConnectPacket::with_v31("a".repeat(24));
+        assert!(!packet.client_identifier_fits_v31());
+        assert!(matches!(
+            packet.validate(),
+            Err(ConnectPacketError::InvalidClientIdentifierLength { length: 24, max: 23 })
+        ));
+        let err = packet.encode(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_connect_packet_protocol_name_level() {
+        use crate::control::variable_header::protocol_level::SPEC_5_0;
+
+        let combinations = [
+            ("MQIsdp", SPEC_3_1_0, true),
+            ("MQTT", SPEC_3_1_1, true),
+            ("MQTT", SPEC_5_0, true),
+            ("MQTT", SPEC_3_1_0, false),
+            ("MQIsdp", SPEC_3_1_1, false),
+            ("mqtt", SPEC_3_1_1, false),
+        ];
+        for &(name, level, valid) in combinations.iter() {
+            let mut buf = Vec::new();
+            ConnectPacket::with_level(name, "12345", level)
+                .unwrap()
+                .encode(&mut buf)
+                .unwrap();
+            match ConnectPacket::decode(&mut Cursor::new(buf)) {
+                Ok(packet) if valid => assert_eq!(packet.protocol_name(), name),
+                Err(PacketError::VariableHeaderError(VariableHeaderError::MismatchedProtocolName(ref n, l)))
+                    if !valid && n == name && l as u8 == level => {}
+                res => panic!("unexpected result for {} {}: {:?}", name, level, res),
+            }
+        }
+    }
+
     #[test]
     fn test_connect_packet_user_name() {
         let mut packet = ConnectPacket::new("12345".to_owned());
@@ -315,4 +775,183 @@ mod test {
 
         assert_eq!(packet, decoded_packet);
     }
+
+    #[test]
+    fn test_connect_packet_will_message() {
+        let mut packet = ConnectPacket::new("12345".to_owned());
+        packet.set_will_message(Some(Will {
+            topic: TopicName::new("a/b").unwrap(),
+            payload: b"offline".to_vec(),
+            qos: QualityOfService::Level1,
+            retain: true,
+        }));
+        assert_eq!(packet.will(), Some(("a/b", &b"offline"[..])));
+        assert_eq!(packet.will_qos(), 1);
+        assert!(packet.will_retain());
+
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+
+        let mut decode_buf = Cursor::new(buf);
+        let decoded_packet = ConnectPacket::decode(&mut decode_buf).unwrap();
+        assert_eq!(packet, decoded_packet);
+
+        let will = decoded_packet.will_message().unwrap();
+        assert_eq!(&will.topic[..], "a/b");
+        assert_eq!(will.payload, b"offline");
+        assert_eq!(will.qos, QualityOfService::Level1);
+        assert!(will.retain);
+
+        packet.set_will(None);
+        assert_eq!(packet.will(), None);
+        assert_eq!(packet.will_message(), None);
+        assert_eq!(packet.will_qos(), 0);
+        assert!(!packet.will_retain());
+        assert_eq!(packet, ConnectPacket::new("12345".to_owned()));
+    }
+
+    #[test]
+    fn test_connect_packet_ref() {
+        let mut packet = ConnectPacket::new("12345".to_owned());
+        packet.set_keep_alive(30);
+        packet.set_user_name(Some("mqtt_player".to_owned()));
+        packet.set_password(Some("secret".to_owned()));
+        packet.set_will_message(Some(Will {
+            topic: TopicName::new("a/b").unwrap(),
+            payload: b"offline".to_vec(),
+            qos: QualityOfService::Level1,
+            retain: true,
+        }));
+
+        let mut packet_ref = ConnectPacketRef::new("12345");
+        packet_ref.set_keep_alive(30);
+        packet_ref.set_user_name(Some("mqtt_player"));
+        packet_ref.set_password(Some("secret"));
+        packet_ref.set_will_message(Some(WillRef {
+            topic: TopicNameRef::new("a/b").unwrap(),
+            payload: b"offline",
+            qos: QualityOfService::Level1,
+            retain: true,
+        }));
+
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+        let mut buf_ref = Vec::new();
+        packet_ref.encode(&mut buf_ref).unwrap();
+
+        assert_eq!(buf, buf_ref);
+        assert_eq!(packet_ref.encoded_length() as usize, buf_ref.len());
+    }
+
+    #[test]
+    fn test_connect_packet_client_identifier() {
+        assert!(ConnectPacket::new("client01").validate_client_id_conformant());
+        assert!(!ConnectPacket::new("").validate_client_id_conformant());
+        assert!(!ConnectPacket::new("client/01").validate_client_id_conformant());
+        assert!(!ConnectPacket::new("a".repeat(24)).validate_client_id_conformant());
+
+        let mut packet = ConnectPacket::new("");
+        assert!(matches!(
+            packet.validate(),
+            Err(ConnectPacketError::EmptyClientIdentifierWithoutCleanSession)
+        ));
+        packet.set_clean_session(true);
+        packet.validate().unwrap();
+    }
+
+    #[test]
+    fn test_connect_packet_password_without_user_name() {
+        let mut packet = ConnectPacket::new("12345".to_owned());
+        packet.set_password(Some("secret".to_owned()));
+        assert!(matches!(
+            packet.validate(),
+            Err(ConnectPacketError::PasswordWithoutUserName)
+        ));
+
+        let mut buf = Vec::new();
+        let err = packet.encode(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        packet.set_user_name(Some("mqtt_player".to_owned()));
+        packet.validate().unwrap();
+        packet.encode(&mut buf).unwrap();
+    }
+
+    #[test]
+    fn test_connect_packet_will_qos_without_will() {
+        let mut packet = ConnectPacket::new("12345".to_owned());
+        packet.flags.will_qos = 1;
+        assert!(matches!(
+            packet.validate(),
+            Err(ConnectPacketError::VariableHeaderError(
+                VariableHeaderError::WillQoSWithoutWillFlag(1)
+            ))
+        ));
+
+        let mut buf = Vec::new();
+        let err = packet.encode(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        packet.flags.will_qos = 0;
+        packet.flags.will_retain = true;
+        assert!(matches!(
+            packet.validate(),
+            Err(ConnectPacketError::WillRetainWithoutWillFlag)
+        ));
+    }
+
+    #[test]
+    fn test_connect_packet_will_setters() {
+        let mut packet = ConnectPacket::new("12345".to_owned());
+        assert!(matches!(
+            packet.set_will_qos(1),
+            Err(ConnectPacketError::VariableHeaderError(
+                VariableHeaderError::WillQoSWithoutWillFlag(1)
+            ))
+        ));
+        assert!(matches!(
+            packet.set_will_retain(true),
+            Err(ConnectPacketError::WillRetainWithoutWillFlag)
+        ));
+        packet.set_will_qos(0).unwrap();
+        packet.set_will_retain(false).unwrap();
+        assert_eq!(packet, ConnectPacket::new("12345".to_owned()));
+
+        packet.set_will(Some((TopicName::new("a/b").unwrap(), b"offline".to_vec())));
+        assert!(matches!(
+            packet.set_will_qos(3),
+            Err(ConnectPacketError::VariableHeaderError(
+                VariableHeaderError::InvalidWillQoS(3)
+            ))
+        ));
+        packet.set_will_qos(2).unwrap();
+        packet.set_will_retain(true).unwrap();
+        assert_eq!(packet.will_qos(), 2);
+        assert!(packet.will_retain());
+        assert_eq!(packet.will(), Some(("a/b", &b"offline"[..])));
+
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+        assert_eq!(ConnectPacket::decode(&mut Cursor::new(buf)).unwrap(), packet);
+
+        packet.set_will(None);
+        assert_eq!(packet, ConnectPacket::new("12345".to_owned()));
+    }
+
+    #[test]
+    fn test_connect_packet_validate_level() {
+        // MQTT 5 allows a password without user name, and an empty client identifier without clean start
+        let mut packet = ConnectPacket::with_level("MQTT", "", 5).unwrap();
+        packet.set_password(Some("secret".to_owned()));
+        packet.validate().unwrap();
+
+        // MQTT v3.1.1 does not
+        let mut packet = ConnectPacket::with_level("MQTT", "", 4).unwrap();
+        assert!(matches!(
+            packet.validate(),
+            Err(ConnectPacketError::EmptyClientIdentifierWithoutCleanSession)
+        ));
+        packet.set_client_identifier("a".repeat(24));
+        packet.validate().unwrap();
+    }
 }
@@ -1,17 +1,27 @@
 //! DISCONNECT
 
-use std::io::Read;
+use alloc::vec::Vec;
+use core::fmt;
 
+use crate::control::variable_header::ProtocolLevel;
 use crate::control::{ControlType, FixedHeader, PacketType};
+use crate::io::Read;
 use crate::packet::{DecodablePacket, PacketError};
+use crate::Decodable;
 
 /// `DISCONNECT` packet
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(remote = "Self")
+)]
 pub struct DisconnectPacket {
     fixed_header: FixedHeader,
 }
 
 encodable_packet!(DisconnectPacket());
+serde_packet!(DisconnectPacket, Disconnect);
 
 impl DisconnectPacket {
     pub fn new() -> DisconnectPacket {
@@ -19,6 +29,27 @@ impl DisconnectPacket {
             fixed_header: FixedHeader::new(PacketType::with_default(ControlType::Disconnect), 0),
         }
     }
+
+    /// Decodes a `DISCONNECT` packet sent by a peer of the given protocol level
+    ///
+    /// Before MQTT 5.0 the packet must not have a body. MQTT 5.0 allows a reason code and
+    /// properties, which are not supported by this crate and skipped.
+    pub fn decode_with_level<R: Read>(reader: &mut R, level: ProtocolLevel) -> Result<Self, PacketError<Self>> {
+        let fixed_header = FixedHeader::decode(reader)?;
+        if level != ProtocolLevel::Version50 {
+            return DisconnectPacket::decode_with(reader, Some(fixed_header));
+        }
+
+        let declared = fixed_header.remaining_length;
+        let body = Vec::<u8>::decode_with(reader, Some(declared))?;
+        if body.len() != declared as usize {
+            return Err(PacketError::LengthMismatch {
+                declared,
+                consumed: body.len() as u32,
+            });
+        }
+        Ok(DisconnectPacket::new())
+    }
 }
 
 impl Default for DisconnectPacket {
@@ -27,10 +58,54 @@ impl Default for DisconnectPacket {
     }
 }
 
+impl fmt::Display for DisconnectPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DISCONNECT")
+    }
+}
+
 impl DecodablePacket for DisconnectPacket {
-    type DecodePacketError = std::convert::Infallible;
+    type DecodePacketError = core::convert::Infallible;
 
     fn decode_packet<R: Read>(_reader: &mut R, fixed_header: FixedHeader) -> Result<Self, PacketError<Self>> {
+        // DISCONNECT has neither variable header nor payload
+        if fixed_header.remaining_length != 0 {
+            return Err(PacketError::LengthMismatch {
+                declared: fixed_header.remaining_length,
+                consumed: 0,
+            });
+        }
         Ok(DisconnectPacket { fixed_header })
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    use std::io::Cursor;
+
+    #[test]
+    fn test_disconnect_packet_non_empty_body() {
+        // DISCONNECT with reason code 0x04 (Disconnect with Will Message)
+        let buf = b"\xe0\x01\x04";
+
+        match DisconnectPacket::decode(&mut Cursor::new(&buf[..])) {
+            Err(PacketError::LengthMismatch {
+                declared: 1,
+                consumed: 0,
+            }) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+        match DisconnectPacket::decode_with_level(&mut Cursor::new(&buf[..]), ProtocolLevel::Version311) {
+            Err(PacketError::LengthMismatch {
+                declared: 1,
+                consumed: 0,
+            }) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        let packet = DisconnectPacket::decode_with_level(&mut Cursor::new(&buf[..]), ProtocolLevel::Version50).unwrap();
+        assert_eq!(packet, DisconnectPacket::new());
+    }
+}
@@ -0,0 +1,101 @@
+//! Server side of the connection handshake
+
+use crate::control::fixed_header::FixedHeaderError;
+use crate::control::{ControlType, FixedHeader};
+use crate::io::Read;
+use crate::packet::{ConnectPacket, PacketError, UnexpectedPacket};
+use crate::Decodable;
+
+/// Receives the first packet of a connection on a server, which must be `CONNECT` [MQTT-3.1.0-1]
+///
+/// ```rust
+/// use mqtt::packet::{ConnectPacket, ServerHandshake};
+/// use mqtt::Encodable;
+///
+/// let mut buf = Vec::new();
+/// ConnectPacket::new("client").encode(&mut buf).unwrap();
+///
+/// let connect = ServerHandshake::decode_first(&mut &buf[..]).unwrap();
+/// assert_eq!(connect.client_identifier(), "client");
+/// ```
+#[derive(Debug)]
+pub struct ServerHandshake;
+
+impl ServerHandshake {
+    /// Decodes exactly one packet, which must be a `CONNECT` with a protocol name matching its
+    /// protocol level
+    ///
+    /// Any other packet type is rejected right after its fixed header, without reading its body.
+    /// The server must close the connection on error.
+    pub fn decode_first<R: Read>(reader: &mut R) -> Result<ConnectPacket, HandshakeError> {
+        let fixed_header = FixedHeader::decode(reader)?;
+        let found = fixed_header.packet_type.control_type();
+        if found != ControlType::Connect {
+            return Err(UnexpectedPacket {
+                expected: ControlType::Connect,
+                found,
+            }
+            .into());
+        }
+
+        Ok(ConnectPacket::decode_with(reader, Some(fixed_header))?)
+    }
+}
+
+/// Errors while receiving the first packet of a connection
+#[derive(Debug, thiserror::Error)]
+pub enum HandshakeError {
+    #[error(transparent)]
+    FixedHeaderError(#[from] FixedHeaderError),
+    #[error("first packet of the connection: {0}")]
+    UnexpectedPacket(#[from] UnexpectedPacket),
+    #[error(transparent)]
+    ConnectPacketError(#[from] PacketError<ConnectPacket>),
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    use crate::control::variable_header::{ProtocolLevel, VariableHeaderError};
+    use crate::packet::PingreqPacket;
+    use crate::Encodable;
+
+    #[test]
+    fn test_server_handshake() {
+        let mut buf = Vec::new();
+        ConnectPacket::with_v31("client").encode(&mut buf).unwrap();
+        let connect = ServerHandshake::decode_first(&mut &buf[..]).unwrap();
+        assert_eq!(connect.protocol_level(), ProtocolLevel::Version310);
+
+        let mut buf = Vec::new();
+        PingreqPacket::new().encode(&mut buf).unwrap();
+        match ServerHandshake::decode_first(&mut &buf[..]) {
+            Err(HandshakeError::UnexpectedPacket(UnexpectedPacket {
+                found: ControlType::PingRequest,
+                ..
+            })) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        // The body of a large first packet is not read
+        let mut rdr = &b"\x30\xFF\xFF\xFF\x7F\x00\x03a/b"[..];
+        assert!(ServerHandshake::decode_first(&mut rdr).is_err());
+        assert_eq!(rdr, b"\x00\x03a/b");
+    }
+
+    #[test]
+    fn test_server_handshake_protocol_name() {
+        let mut buf = Vec::new();
+        ConnectPacket::with_level("MQIsdp", "client", 4)
+            .unwrap()
+            .encode(&mut buf)
+            .unwrap();
+        match ServerHandshake::decode_first(&mut &buf[..]) {
+            Err(HandshakeError::ConnectPacketError(PacketError::VariableHeaderError(
+                VariableHeaderError::MismatchedProtocolName(_, ProtocolLevel::Version311),
+            ))) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+}
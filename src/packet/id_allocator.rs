@@ -0,0 +1,123 @@
+//! Packet identifier allocation
+
+use alloc::{boxed::Box, vec};
+
+const WORDS: usize = (u16::MAX as usize + 1) / 64;
+
+/// Allocator of packet identifiers for in-flight `QoS 1` and `QoS 2` packets
+///
+/// Identifiers are in `1..=65535`, never 0. They are handed out in increasing order and wrap
+/// around, so a released identifier is not reused right away.
+///
+/// ```rust
+/// use mqtt::packet::PacketIdAllocator;
+///
+/// let mut ids = PacketIdAllocator::new();
+/// let pkid = ids.allocate().unwrap();
+/// assert!(ids.is_in_use(pkid));
+///
+/// // PUBACK received
+/// assert!(ids.release(pkid));
+/// assert!(!ids.is_in_use(pkid));
+/// ```
+#[derive(Debug, Clone)]
+pub struct PacketIdAllocator {
+    // Bit for identifier 0 is always set, so it is never allocated
+    in_use: Box<[u64]>,
+    next: u16,
+    len: usize,
+}
+
+impl PacketIdAllocator {
+    pub fn new() -> PacketIdAllocator {
+        let mut in_use = vec![0; WORDS].into_boxed_slice();
+        in_use[0] = 1;
+        PacketIdAllocator {
+            in_use,
+            next: 1,
+            len: 0,
+        }
+    }
+
+    /// Marks a free identifier as in use and returns it, or `None` if all identifiers are in use
+    pub fn allocate(&mut self) -> Option<u16> {
+        if self.len == u16::MAX as usize {
+            return None;
+        }
+
+        let mut word = self.next as usize / 64;
+        let mut free = !self.in_use[word] & (!0u64 << (self.next as usize % 64));
+        while free == 0 {
+            word = (word + 1) % WORDS;
+            free = !self.in_use[word];
+        }
+
+        let id = (word * 64 + free.trailing_zeros() as usize) as u16;
+        self.in_use[word] |= 1 << (id % 64);
+        self.len += 1;
+        self.next = id.wrapping_add(1);
+        Some(id)
+    }
+
+    /// Frees an identifier for reuse. Returns `false` if it was not in use.
+    pub fn release(&mut self, id: u16) -> bool {
+        if !self.is_in_use(id) {
+            return false;
+        }
+        self.in_use[id as usize / 64] &= !(1 << (id % 64));
+        self.len -= 1;
+        true
+    }
+
+    /// Checks whether an identifier is allocated and not released yet
+    pub fn is_in_use(&self, id: u16) -> bool {
+        id != 0 && self.in_use[id as usize / 64] & (1 << (id % 64)) != 0
+    }
+
+    /// Number of identifiers in use
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Default for PacketIdAllocator {
+    fn default() -> PacketIdAllocator {
+        PacketIdAllocator::new()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_packet_id_allocator_sequential() {
+        let mut ids = PacketIdAllocator::new();
+        assert_eq!(ids.allocate(), Some(1));
+        assert_eq!(ids.allocate(), Some(2));
+        assert!(ids.release(1));
+        assert!(!ids.release(1));
+
+        // Released identifiers are not reused before wrapping around
+        assert_eq!(ids.allocate(), Some(3));
+        assert_eq!(ids.len(), 2);
+    }
+
+    #[test]
+    fn test_packet_id_allocator_exhausted() {
+        let mut ids = PacketIdAllocator::new();
+        for expected in 1..=u16::MAX {
+            assert_eq!(ids.allocate(), Some(expected));
+        }
+        assert_eq!(ids.allocate(), None);
+        assert!(!ids.is_in_use(0));
+
+        assert!(ids.release(100));
+        assert_eq!(ids.allocate(), Some(100));
+        assert_eq!(ids.allocate(), None);
+    }
+}
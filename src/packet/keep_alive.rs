@@ -0,0 +1,234 @@
+//! Keep alive timing for clients
+
+use std::cmp;
+use std::time::{Duration, Instant};
+
+use crate::packet::ConnectPacket;
+
+/// What a client has to do to keep the connection alive, returned by `KeepAliveTracker::poll`
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum KeepAliveAction {
+    /// Keep alive is disabled, nothing will ever be due
+    Idle,
+    /// Nothing to do before this instant, `poll` again then
+    WaitUntil(Instant),
+    /// A `PINGREQ` must be sent now
+    SendPingreq,
+    /// The `PINGRESP` did not arrive in time, the connection should be closed
+    Timeout,
+}
+
+/// Computes when a client has to send `PINGREQ` packets, independent of the transport
+///
+/// The client must send a control packet at least once per keep alive interval, so a `PINGREQ` is
+/// due when nothing has been sent for that long [MQTT-3.1.2-23]. A `PINGREQ` is also sent when
+/// nothing has been received for an interval, to check that the server is still there. If the
+/// `PINGRESP` does not arrive within half an interval, the connection is considered dead. A
+/// silent server is thus detected one and a half times the keep alive interval after its last
+/// packet, the grace period a server allows a silent client [MQTT-3.1.2-24].
+///
+/// ```rust
+/// use std::time::{Duration, Instant};
+///
+/// use mqtt::packet::{KeepAliveAction, KeepAliveTracker};
+///
+/// let start = Instant::now();
+/// let mut tracker = KeepAliveTracker::new(10, start);
+/// assert_eq!(tracker.poll(start), KeepAliveAction::WaitUntil(start + Duration::from_secs(10)));
+///
+/// let later = start + Duration::from_secs(10);
+/// assert_eq!(tracker.poll(later), KeepAliveAction::SendPingreq);
+/// tracker.note_pingresp();
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeepAliveTracker {
+    keep_alive: Option<Duration>,
+    last_sent: Instant,
+    last_received: Instant,
+    pingreq_sent: Option<Instant>,
+}
+
+impl KeepAliveTracker {
+    /// Creates a tracker for a connection established at `now`, `keep_alive` is in seconds and
+    /// 0 disables the mechanism
+    pub fn new(keep_alive: u16, now: Instant) -> KeepAliveTracker {
+        KeepAliveTracker {
+            keep_alive: match keep_alive {
+                0 => None,
+                secs => Some(Duration::from_secs(secs.into())),
+            },
+            last_sent: now,
+            last_received: now,
+            pingreq_sent: None,
+        }
+    }
+
+    /// Creates a tracker with the keep alive requested in `packet`, sent at `now`
+    pub fn from_connect(packet: &ConnectPacket, now: Instant) -> KeepAliveTracker {
+        KeepAliveTracker::new(packet.keep_alive(), now)
+    }
+
+    /// Records that a control packet has been sent, which postpones the next `PINGREQ`
+    pub fn note_packet_sent(&mut self, now: Instant) {
+        self.last_sent = now;
+    }
+
+    /// Records that a control packet has been received, including `PINGRESP`
+    pub fn note_packet_received(&mut self, now: Instant) {
+        self.last_received = now;
+    }
+
+    /// Records that the `PINGRESP` has been received
+    pub fn note_pingresp(&mut self) {
+        self.pingreq_sent = None;
+    }
+
+    /// Whether the last `PINGREQ` has not been answered within half the keep alive interval
+    pub fn is_timed_out(&self, now: Instant) -> bool {
+        match (self.keep_alive, self.pingreq_sent) {
+            (Some(keep_alive), Some(sent)) => now >= sent + keep_alive / 2,
+            _ => false,
+        }
+    }
+
+    /// Returns what has to be done at `now`
+    ///
+    /// When `KeepAliveAction::SendPingreq` is returned the tracker assumes the `PINGREQ` is sent
+    /// right away, and starts waiting for its `PINGRESP`.
+    pub fn poll(&mut self, now: Instant) -> KeepAliveAction {
+        let keep_alive = match self.keep_alive {
+            Some(keep_alive) => keep_alive,
+            None => return KeepAliveAction::Idle,
+        };
+
+        if let Some(sent) = self.pingreq_sent {
+            let deadline = sent + keep_alive / 2;
+            return if now >= deadline {
+                KeepAliveAction::Timeout
+            } else {
+                KeepAliveAction::WaitUntil(deadline)
+            };
+        }
+
+        // A client that keeps sending may never need a PINGREQ, but a silent server is still probed
+        let next_pingreq = cmp::min(self.last_sent, self.last_received) + keep_alive;
+        if now >= next_pingreq {
+            self.last_sent = now;
+            self.pingreq_sent = Some(now);
+            KeepAliveAction::SendPingreq
+        } else {
+            KeepAliveAction::WaitUntil(next_pingreq)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_keep_alive_pingreq() {
+        let start = Instant::now();
+        let secs = Duration::from_secs;
+        let mut tracker = KeepAliveTracker::new(10, start);
+
+        // Sending any packet postpones the PINGREQ
+        tracker.note_packet_sent(start + secs(4));
+        tracker.note_packet_received(start + secs(4));
+        assert_eq!(
+            tracker.poll(start + secs(10)),
+            KeepAliveAction::WaitUntil(start + secs(14))
+        );
+        assert_eq!(tracker.poll(start + secs(14)), KeepAliveAction::SendPingreq);
+
+        // Waiting for PINGRESP
+        assert_eq!(
+            tracker.poll(start + secs(15)),
+            KeepAliveAction::WaitUntil(start + secs(19))
+        );
+        assert!(!tracker.is_timed_out(start + secs(18)));
+        tracker.note_packet_received(start + secs(16));
+        tracker.note_pingresp();
+        assert_eq!(
+            tracker.poll(start + secs(16)),
+            KeepAliveAction::WaitUntil(start + secs(24))
+        );
+        assert_eq!(tracker.poll(start + secs(24)), KeepAliveAction::SendPingreq);
+
+        // Other packets arrive, but no PINGRESP
+        tracker.note_packet_received(start + secs(26));
+        assert_eq!(
+            tracker.poll(start + secs(28)),
+            KeepAliveAction::WaitUntil(start + secs(29))
+        );
+        assert!(tracker.is_timed_out(start + secs(29)));
+        assert_eq!(tracker.poll(start + secs(29)), KeepAliveAction::Timeout);
+    }
+
+    #[test]
+    fn test_keep_alive_disabled() {
+        let start = Instant::now();
+        let mut tracker = KeepAliveTracker::from_connect(&ConnectPacket::new("client"), start);
+        assert_eq!(tracker.poll(start + Duration::from_secs(3600)), KeepAliveAction::Idle);
+
+        let mut connect = ConnectPacket::new("client");
+        connect.set_keep_alive(60);
+        let mut tracker = KeepAliveTracker::from_connect(&connect, start);
+        assert_eq!(
+            tracker.poll(start),
+            KeepAliveAction::WaitUntil(start + Duration::from_secs(60))
+        );
+    }
+
+    #[test]
+    fn test_keep_alive_busy_client() {
+        let start = Instant::now();
+        let secs = Duration::from_secs;
+        let mut tracker = KeepAliveTracker::new(10, start);
+
+        // QoS 0 messages are published continuously, the server has nothing to send back
+        for t in 1..10 {
+            tracker.note_packet_sent(start + secs(t));
+            assert_eq!(
+                tracker.poll(start + secs(t)),
+                KeepAliveAction::WaitUntil(start + secs(10))
+            );
+        }
+
+        // Nothing has been received for an interval, so the server is probed, and it answers
+        assert_eq!(tracker.poll(start + secs(10)), KeepAliveAction::SendPingreq);
+        tracker.note_packet_received(start + secs(11));
+        tracker.note_pingresp();
+        assert!(!tracker.is_timed_out(start + secs(16)));
+        assert_eq!(
+            tracker.poll(start + secs(16)),
+            KeepAliveAction::WaitUntil(start + secs(20))
+        );
+    }
+
+    #[test]
+    fn test_keep_alive_silent_server() {
+        let start = Instant::now();
+        let secs = Duration::from_secs;
+        let mut tracker = KeepAliveTracker::new(10, start);
+
+        // Packets keep being sent, but the server stops answering
+        tracker.note_packet_received(start + secs(2));
+        tracker.note_packet_sent(start + secs(9));
+        assert_eq!(
+            tracker.poll(start + secs(9)),
+            KeepAliveAction::WaitUntil(start + secs(12))
+        );
+        assert_eq!(tracker.poll(start + secs(12)), KeepAliveAction::SendPingreq);
+
+        // The PINGREQ is unanswered, 1.5 times the keep alive after the last received packet
+        tracker.note_packet_sent(start + secs(16));
+        assert!(!tracker.is_timed_out(start + secs(16)));
+        assert_eq!(
+            tracker.poll(start + secs(16)),
+            KeepAliveAction::WaitUntil(start + secs(17))
+        );
+        assert!(tracker.is_timed_out(start + secs(17)));
+        assert_eq!(tracker.poll(start + secs(17)), KeepAliveAction::Timeout);
+    }
+}
@@ -1,35 +1,46 @@
 //! Specific packets
 
-use std::error::Error;
-use std::fmt::{self, Debug};
-use std::io::{self, Read, Write};
+use alloc::{borrow::ToOwned, boxed::Box, format, string::String, vec, vec::Vec};
+use core::convert::TryFrom;
+use core::fmt::{self, Debug};
 
+#[cfg(feature = "bytes")]
+use bytes::BytesMut;
 #[cfg(feature = "tokio")]
-use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-use crate::control::fixed_header::FixedHeaderError;
+use crate::control::fixed_header::{FixedHeaderError, MAX_REMAINING_LENGTH};
 use crate::control::variable_header::VariableHeaderError;
 use crate::control::ControlType;
 use crate::control::FixedHeader;
+use crate::io::{self, Read, Write};
+use crate::packet::publish::PublishPacketError;
+use crate::packet::suback::SubackPacketError;
+use crate::packet::subscribe::SubscribePacketError;
 use crate::topic_name::{TopicNameDecodeError, TopicNameError};
 use crate::{Decodable, Encodable};
 
+// With `validate = method`, `self.method()` is checked before encoding and its error is returned as
+// `io::ErrorKind::InvalidData`
 macro_rules! encodable_packet {
-    ($typ:ident($($field:ident),* $(,)?)) => {
+    ($typ:ident($($field:ident),* $(,)?) $(, validate = $validate:ident)?) => {
         impl $crate::packet::EncodablePacket for $typ {
             fn fixed_header(&self) -> &$crate::control::fixed_header::FixedHeader {
                 &self.fixed_header
             }
 
             #[allow(unused)]
-            fn encode_packet<W: ::std::io::Write>(&self, writer: &mut W) -> ::std::io::Result<()> {
+            fn encode_packet<W: $crate::io::Write>(&self, writer: &mut W) -> $crate::io::Result<()> {
+                $(
+                    self.$validate()
+                        .map_err(|err| $crate::io::Error::new($crate::io::ErrorKind::InvalidData, err))?;
+                )?
                 $($crate::encodable::Encodable::encode(&self.$field, writer)?;)*
                 Ok(())
             }
 
             fn encoded_packet_length(&self) -> u32 {
-                $($crate::encodable::Encodable::encoded_length(&self.$field) +)*
-                    0
+                0u32 $(.saturating_add($crate::encodable::Encodable::encoded_length(&self.$field)))*
             }
         }
 
@@ -39,13 +50,57 @@ macro_rules! encodable_packet {
             fn fix_header_remaining_len(&mut self) {
                 self.fixed_header.remaining_length = $crate::packet::EncodablePacket::encoded_packet_length(self);
             }
+
+            /// Names and lengths of the encoded fields after fixed header
+            pub(crate) fn encoded_fields(&self) -> ::alloc::vec::Vec<(&'static str, u32)> {
+                ::alloc::vec![$((stringify!($field), $crate::encodable::Encodable::encoded_length(&self.$field)),)*]
+            }
+        }
+    };
+}
+
+// Serde impls for a packet deriving them with `serde(remote = "Self")`. The derived `Deserialize`
+// trusts the serialized fixed header, so its packet type is checked and its remaining length is
+// computed again from the other fields.
+#[cfg(feature = "serde")]
+macro_rules! serde_packet {
+    ($typ:ident, $control_type:ident) => {
+        impl serde::Serialize for $typ {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                $typ::serialize(self, serializer)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $typ {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<$typ, D::Error> {
+                let mut packet = $typ::deserialize(deserializer)?;
+                let control_type = packet.fixed_header.packet_type.control_type();
+                if control_type != $crate::control::ControlType::$control_type {
+                    return Err(<D::Error as serde::de::Error>::custom(format_args!(
+                        "packet type {:?} does not match {}",
+                        control_type,
+                        stringify!($typ)
+                    )));
+                }
+                packet.fix_header_remaining_len();
+                Ok(packet)
+            }
         }
     };
 }
 
+#[cfg(not(feature = "serde"))]
+macro_rules! serde_packet {
+    ($typ:ident, $control_type:ident) => {};
+}
+
 pub use self::connack::ConnackPacket;
-pub use self::connect::ConnectPacket;
+pub use self::connect::{ConnectPacket, ConnectPacketRef, Will, WillRef};
 pub use self::disconnect::DisconnectPacket;
+pub use self::handshake::ServerHandshake;
+pub use self::id_allocator::PacketIdAllocator;
+#[cfg(feature = "std")]
+pub use self::keep_alive::{KeepAliveAction, KeepAliveTracker};
 pub use self::pingreq::PingreqPacket;
 pub use self::pingresp::PingrespPacket;
 pub use self::puback::PubackPacket;
@@ -53,16 +108,26 @@ pub use self::pubcomp::PubcompPacket;
 pub use self::publish::{PublishPacket, PublishPacketRef};
 pub use self::pubrec::PubrecPacket;
 pub use self::pubrel::PubrelPacket;
+pub use self::qos2::QoS2State;
+#[cfg(feature = "tokio")]
+pub use self::shared_sink::SharedPacketSink;
 pub use self::suback::SubackPacket;
-pub use self::subscribe::SubscribePacket;
+pub use self::subscribe::{SubscribePacket, SubscribePacketRef};
+#[cfg(feature = "test-util")]
+pub use self::test_support::sample_packets;
 pub use self::unsuback::UnsubackPacket;
 pub use self::unsubscribe::UnsubscribePacket;
 
 pub use self::publish::QoSWithPacketIdentifier;
+pub use self::stream::{PacketReader, PacketWriter};
 
 pub mod connack;
 pub mod connect;
 pub mod disconnect;
+pub mod handshake;
+pub mod id_allocator;
+#[cfg(feature = "std")]
+pub mod keep_alive;
 pub mod pingreq;
 pub mod pingresp;
 pub mod puback;
@@ -70,8 +135,14 @@ pub mod pubcomp;
 pub mod publish;
 pub mod pubrec;
 pub mod pubrel;
+pub mod qos2;
+#[cfg(feature = "tokio")]
+pub mod shared_sink;
+pub mod stream;
 pub mod suback;
 pub mod subscribe;
+#[cfg(feature = "test-util")]
+pub mod test_support;
 pub mod unsuback;
 pub mod unsubscribe;
 
@@ -92,21 +163,119 @@ pub trait EncodablePacket {
     fn encoded_packet_length(&self) -> u32 {
         0
     }
+
+    /// Length in bytes of the whole packet, including the fixed header
+    ///
+    /// Same as `Encodable::encoded_length`, available to size any packet before sending it, e.g.
+    /// against a maximum packet size.
+    fn total_encoded_length(&self) -> u32 {
+        self.fixed_header()
+            .encoded_length()
+            .saturating_add(self.encoded_packet_length())
+    }
+
+    /// Length in bytes of the whole packet, or an error if the data after fixed header cannot be
+    /// represented by the remaining length field
+    fn try_encoded_length(&self) -> Result<u32, LengthError> {
+        let remaining_length = self.encoded_packet_length();
+        if remaining_length > MAX_REMAINING_LENGTH {
+            return Err(LengthError(remaining_length));
+        }
+        Ok(self.fixed_header().encoded_length() + remaining_length)
+    }
+
+    /// Appends the encoded packet to `buf`, returning the number of bytes written
+    ///
+    /// The buffer is reserved up front with the exact length of the packet. Writing to a `Vec` never
+    /// fails, so errors are only returned for packets that cannot be encoded, as with `encode`. The
+    /// buffer is left unchanged in that case.
+    fn encode_into(&self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let length = self
+            .try_encoded_length()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))? as usize;
+        buf.reserve(length);
+
+        let start = buf.len();
+        if let Err(err) = self.fixed_header().encode(buf).and_then(|_| self.encode_packet(buf)) {
+            buf.truncate(start);
+            return Err(err);
+        }
+
+        let written = buf.len() - start;
+        debug_assert_eq!(written, length, "encoded length mismatch");
+        Ok(written)
+    }
+
+    /// Encodes the packet at the beginning of `buf`, returning the number of bytes written
+    ///
+    /// This does not allocate, e.g. for sending acknowledgements from a stack buffer on embedded
+    /// targets. If the packet does not fit, nothing is written and an error of kind
+    /// `io::ErrorKind::WriteZero` is returned, wrapping a [`BufferTooSmall`].
+    fn encode_to_slice(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let length = self
+            .try_encoded_length()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))? as usize;
+        if length > buf.len() {
+            let err = BufferTooSmall {
+                required: length,
+                available: buf.len(),
+            };
+            return Err(io::Error::new(io::ErrorKind::WriteZero, err));
+        }
+
+        let mut writer = &mut buf[..length];
+        self.fixed_header().encode(&mut writer)?;
+        self.encode_packet(&mut writer)?;
+        debug_assert!(writer.is_empty(), "encoded length mismatch");
+        Ok(length)
+    }
+}
+
+impl<T: EncodablePacket + ?Sized> EncodablePacket for &T {
+    fn fixed_header(&self) -> &FixedHeader {
+        (**self).fixed_header()
+    }
+
+    fn encode_packet<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        (**self).encode_packet(writer)
+    }
+
+    fn encoded_packet_length(&self) -> u32 {
+        (**self).encoded_packet_length()
+    }
 }
 
 impl<T: EncodablePacket> Encodable for T {
     fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.try_encoded_length()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
         self.fixed_header().encode(writer)?;
         self.encode_packet(writer)
     }
 
     fn encoded_length(&self) -> u32 {
-        self.fixed_header().encoded_length() + self.encoded_packet_length()
+        self.total_encoded_length()
     }
 }
 
+/// Buffer too small for the encoded packet
+#[derive(Debug, Eq, PartialEq, Copy, Clone, thiserror::Error)]
+#[error("packet of {required} bytes does not fit in a buffer of {available} bytes")]
+pub struct BufferTooSmall {
+    pub required: usize,
+    pub available: usize,
+}
+
+/// Packet is too large to be encoded
+#[derive(Debug, thiserror::Error)]
+#[error("remaining length {0} exceeds the maximum of {max}", max = MAX_REMAINING_LENGTH)]
+pub struct LengthError(pub u32);
+
 pub trait DecodablePacket: EncodablePacket + Sized {
-    type DecodePacketError: Error + 'static;
+    #[cfg(feature = "std")]
+    type DecodePacketError: std::error::Error + 'static;
+    #[cfg(not(feature = "std"))]
+    type DecodePacketError: Debug + 'static;
 
     /// Decode packet given a `FixedHeader`
     fn decode_packet<R: Read>(reader: &mut R, fixed_header: FixedHeader) -> Result<Self, PacketError<Self>>;
@@ -123,22 +292,45 @@ impl<T: DecodablePacket> Decodable for T {
             Decodable::decode(reader)?
         };
 
-        <Self as DecodablePacket>::decode_packet(reader, fixed_header)
+        decode_packet_exact(reader, fixed_header)
     }
 }
 
+/// Decode packet given a `FixedHeader`, checking that exactly `remaining_length` bytes are consumed
+fn decode_packet_exact<P: DecodablePacket, R: Read>(
+    reader: &mut R,
+    fixed_header: FixedHeader,
+) -> Result<P, PacketError<P>> {
+    let declared = fixed_header.remaining_length;
+    let reader = &mut reader.take(u64::from(declared));
+    let packet = <P as DecodablePacket>::decode_packet(reader, fixed_header)?;
+
+    let consumed = declared - reader.limit() as u32;
+    if consumed != declared {
+        return Err(PacketError::LengthMismatch { declared, consumed });
+    }
+    Ok(packet)
+}
+
 /// Parsing errors for packet
 #[derive(thiserror::Error)]
-#[error(transparent)]
 pub enum PacketError<P>
 where
     P: DecodablePacket,
 {
+    #[error(transparent)]
     FixedHeaderError(#[from] FixedHeaderError),
+    #[error(transparent)]
     VariableHeaderError(#[from] VariableHeaderError),
-    PayloadError(<P as DecodablePacket>::DecodePacketError),
+    /// The payload error is also returned by `source()`, so it can be downcast from any wrapping error
+    #[error("{0}")]
+    PayloadError(#[source] <P as DecodablePacket>::DecodePacketError),
+    #[error(transparent)]
     IoError(#[from] io::Error),
+    #[error(transparent)]
     TopicNameError(#[from] TopicNameError),
+    #[error("remaining length is {declared} but packet consumed {consumed} bytes")]
+    LengthMismatch { declared: u32, consumed: u32 },
 }
 
 impl<P> Debug for PacketError<P>
@@ -152,6 +344,11 @@ where
             PacketError::PayloadError(ref e) => f.debug_tuple("PayloadError").field(e).finish(),
             PacketError::IoError(ref e) => f.debug_tuple("IoError").field(e).finish(),
             PacketError::TopicNameError(ref e) => f.debug_tuple("TopicNameError").field(e).finish(),
+            PacketError::LengthMismatch { declared, consumed } => f
+                .debug_struct("LengthMismatch")
+                .field("declared", &declared)
+                .field("consumed", &consumed)
+                .finish(),
         }
     }
 }
@@ -166,9 +363,10 @@ impl<P: DecodablePacket> From<TopicNameDecodeError> for PacketError<P> {
 }
 
 macro_rules! impl_variable_packet {
-    ($($name:ident & $errname:ident => $hdr:ident,)+) => {
+    ($($name:ident & $errname:ident => $hdr:ident / $into:ident,)+) => {
         /// Variable packet
-        #[derive(Debug, Eq, PartialEq, Clone)]
+        #[derive(Debug, Eq, PartialEq, Hash, Clone)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         pub enum VariablePacket {
             $(
                 $name($name),
@@ -181,13 +379,108 @@ macro_rules! impl_variable_packet {
             ///
             /// This requires mqtt-rs to be built with `feature = "tokio"`
             pub async fn parse<A: AsyncRead + Unpin>(rdr: &mut A) -> Result<Self, VariablePacketError> {
-                use std::io::Cursor;
                 let fixed_header = FixedHeader::parse(rdr).await?;
+                VariablePacket::parse_body(rdr, fixed_header).await
+            }
+
+            /// Like `parse`, but fails with `VariablePacketError::Timeout` if the whole packet has
+            /// not been received by `deadline`, e.g. to supervise the keep alive of a connection
+            ///
+            /// The bytes of a packet that was only partially received are lost on timeout, so the
+            /// connection should be closed.
+            ///
+            /// This requires mqtt-rs to be built with `feature = "tokio"`
+            pub async fn parse_with_deadline<A: AsyncRead + Unpin>(
+                rdr: &mut A,
+                deadline: tokio::time::Instant,
+            ) -> Result<Self, VariablePacketError> {
+                tokio::time::timeout_at(deadline, VariablePacket::parse(rdr))
+                    .await
+                    .map_err(|_| VariablePacketError::Timeout)?
+            }
+
+            /// Asynchronously parse a packet from a `tokio::io::AsyncBufRead`
+            ///
+            /// When the whole packet is already in the reader's buffer, it is decoded from there
+            /// without being copied into an intermediate `Vec`.
+            ///
+            /// This requires mqtt-rs to be built with `feature = "tokio"`
+            pub async fn parse_buffered<A: AsyncBufRead + Unpin>(rdr: &mut A) -> Result<Self, VariablePacketError> {
+                let fixed_header = FixedHeader::parse(rdr).await?;
+
+                let remaining_length = fixed_header.remaining_length as usize;
+                if remaining_length == 0 {
+                    // Waiting for the buffer to fill would block until the peer sends another packet
+                    return decode_with_header(&mut &[][..], fixed_header);
+                }
+                let buf = rdr.fill_buf().await?;
+                if buf.len() >= remaining_length {
+                    let packet = decode_with_header(&mut &buf[..remaining_length], fixed_header);
+                    rdr.consume(remaining_length);
+                    return packet;
+                }
+
+                VariablePacket::parse_body(rdr, fixed_header).await
+            }
+
+            /// Like `parse`, but reads the packet body into `scratch` instead of a new `Vec`, so the
+            /// same allocation serves every packet of a connection
+            ///
+            /// The previous content of `scratch` is discarded. The returned packet owns its data.
+            ///
+            /// This requires mqtt-rs to be built with `feature = "tokio"`
+            pub async fn parse_into<A: AsyncRead + Unpin>(
+                rdr: &mut A,
+                scratch: &mut Vec<u8>,
+            ) -> Result<Self, VariablePacketError> {
+                let fixed_header = FixedHeader::parse(rdr).await?;
+                scratch.clear();
+                VariablePacket::read_body(rdr, fixed_header, scratch).await
+            }
 
-                let mut buffer = vec![0u8; fixed_header.remaining_length as usize];
-                rdr.read_exact(&mut buffer).await?;
+            async fn parse_body<A: AsyncRead + Unpin>(
+                rdr: &mut A,
+                fixed_header: FixedHeader,
+            ) -> Result<Self, VariablePacketError> {
+                let mut buffer = Vec::new();
+                VariablePacket::read_body(rdr, fixed_header, &mut buffer).await
+            }
+
+            async fn read_body<A: AsyncRead + Unpin>(
+                rdr: &mut A,
+                fixed_header: FixedHeader,
+                buffer: &mut Vec<u8>,
+            ) -> Result<Self, VariablePacketError> {
+                let remaining_length = fixed_header.remaining_length;
+                buffer.reserve(remaining_length as usize);
+                rdr.take(remaining_length.into()).read_to_end(buffer).await?;
+                if buffer.len() < remaining_length as usize {
+                    return Err(VariablePacketError::IncompletePacket {
+                        remaining_length,
+                        received: buffer.len() as u32,
+                    });
+                }
 
-                decode_with_header(&mut Cursor::new(buffer), fixed_header)
+                decode_with_header(&mut &buffer[..], fixed_header)
+            }
+
+            /// Asynchronously write a packet to a `tokio::io::AsyncWrite`
+            ///
+            /// This requires mqtt-rs to be built with `feature = "tokio"`
+            pub async fn write_to<A: AsyncWrite + Unpin>(&self, wr: &mut A) -> io::Result<()> {
+                self.try_encoded_length()
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                self.fixed_header().write_to(wr).await?;
+
+                match *self {
+                    // Payloads may be large, write them directly instead of buffering
+                    VariablePacket::PublishPacket(ref pk) => pk.write_packet_to(wr).await,
+                    ref pk => {
+                        let mut buffer = Vec::with_capacity(pk.encoded_packet_length() as usize);
+                        pk.encode_packet(&mut buffer)?;
+                        wr.write_all(&buffer).await
+                    }
+                }
             }
         }
 
@@ -196,10 +489,16 @@ macro_rules! impl_variable_packet {
             match fixed_header.packet_type.control_type() {
                 $(
                     ControlType::$hdr => {
-                        let pk = <$name as DecodablePacket>::decode_packet(rdr, fixed_header)?;
+                        let pk = decode_packet_exact::<$name, _>(rdr, fixed_header)?;
                         Ok(VariablePacket::$name(pk))
                     }
                 )+
+                // MQTT 5 only, there is no packet for it
+                ControlType::Auth => {
+                    let mut buf = Vec::new();
+                    rdr.take(fixed_header.remaining_length.into()).read_to_end(&mut buf)?;
+                    Err(VariablePacketError::ReservedPacket(fixed_header.packet_type.to_u8() >> 4, buf))
+                }
             }
         }
 
@@ -209,6 +508,18 @@ macro_rules! impl_variable_packet {
                     VariablePacket::$name(pk)
                 }
             }
+
+            /// Gives the packet back unchanged if it is of another type
+            impl TryFrom<VariablePacket> for $name {
+                type Error = VariablePacket;
+
+                fn try_from(pk: VariablePacket) -> Result<$name, VariablePacket> {
+                    match pk {
+                        VariablePacket::$name(pk) => Ok(pk),
+                        pk => Err(pk),
+                    }
+                }
+            }
         )+
 
         // impl Encodable for VariablePacket {
@@ -229,6 +540,39 @@ macro_rules! impl_variable_packet {
         //     }
         // }
 
+        impl VariablePacket {
+            $(
+                #[doc = concat!("Returns the inner `", stringify!($name), "`, or an error with the actual control type")]
+                pub fn $into(self) -> Result<$name, UnexpectedPacket> {
+                    match self {
+                        VariablePacket::$name(pk) => Ok(pk),
+                        pk => Err(UnexpectedPacket {
+                            expected: ControlType::$hdr,
+                            found: pk.control_type(),
+                        }),
+                    }
+                }
+            )+
+
+            fn encoded_fields(&self) -> Vec<(&'static str, u32)> {
+                match *self {
+                    $(
+                        VariablePacket::$name(ref pk) => pk.encoded_fields(),
+                    )+
+                }
+            }
+        }
+
+        impl fmt::Display for VariablePacket {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match *self {
+                    $(
+                        VariablePacket::$name(ref pk) => fmt::Display::fmt(pk, f),
+                    )+
+                }
+            }
+        }
+
         impl EncodablePacket for VariablePacket {
             fn fixed_header(&self) -> &FixedHeader {
                 match *self {
@@ -276,9 +620,18 @@ macro_rules! impl_variable_packet {
                         }
                     }
                 };
-                let reader = &mut reader.take(fixed_header.remaining_length as u64);
 
-                decode_with_header(reader, fixed_header)
+                let mut reader = OffsetReader::new(reader);
+                decode_with_header(&mut reader, fixed_header).map_err(|err| {
+                    if reader.eof {
+                        VariablePacketError::IncompletePacket {
+                            remaining_length: fixed_header.remaining_length,
+                            received: reader.offset as u32,
+                        }
+                    } else {
+                        err
+                    }
+                })
             }
         }
 
@@ -287,38 +640,78 @@ macro_rules! impl_variable_packet {
         pub enum VariablePacketError {
             #[error(transparent)]
             FixedHeaderError(#[from] FixedHeaderError),
-            #[error("reserved packet type ({0}), [u8, ..{}]", .1.len())]
+            #[error("reserved packet type ({0}), [u8, ..{len}]", len = .1.len())]
             ReservedPacket(u8, Vec<u8>),
+            /// The stream ended after the fixed header, before the whole packet was received
+            #[error("stream ended after {received} of {remaining_length} bytes of packet")]
+            IncompletePacket { remaining_length: u32, received: u32 },
             #[error(transparent)]
             IoError(#[from] io::Error),
+            #[error("byte {offset}: {error}")]
+            AtOffset { offset: u64, error: Box<VariablePacketError> },
+            /// The packet is larger than the maximum packet size allowed by the decoder
+            #[error("packet size {size} exceeds the maximum of {max}")]
+            PacketTooLarge { size: u32, max: u32 },
+            /// No whole packet was received before the deadline
+            #[error("timed out waiting for a packet")]
+            Timeout,
             $(
                 #[error(transparent)]
                 $errname(#[from] PacketError<$name>),
             )+
         }
+
+        impl VariablePacketError {
+            /// Offset of the byte that caused this error, relative to the start of the packet, given
+            /// `consumed` bytes have been read from the packet
+            fn invalid_byte_offset(&self, consumed: u64) -> Option<u64> {
+                match *self {
+                    // Packet type and flags are in the first byte of the packet
+                    VariablePacketError::FixedHeaderError(FixedHeaderError::PacketTypeError(..)) => Some(0),
+                    VariablePacketError::PublishPacketError(PacketError::PayloadError(
+                        PublishPacketError::InvalidDupForQoS0,
+                    )) => Some(0),
+                    VariablePacketError::SubscribePacketError(PacketError::PayloadError(
+                        SubscribePacketError::InvalidQualityOfService,
+                    )) => consumed.checked_sub(1),
+                    VariablePacketError::SubackPacketError(PacketError::PayloadError(
+                        SubackPacketError::InvalidSubscribeReturnCode(..),
+                    )) => consumed.checked_sub(1),
+                    $(
+                        VariablePacketError::$errname(PacketError::VariableHeaderError(
+                            VariableHeaderError::InvalidReservedFlag
+                                | VariableHeaderError::InvalidProtocolVersion
+                                | VariableHeaderError::InvalidWillQoS(..)
+                                | VariableHeaderError::WillQoSWithoutWillFlag(..),
+                        )) => consumed.checked_sub(1),
+                    )+
+                    _ => None,
+                }
+            }
+        }
     }
 }
 
 impl_variable_packet! {
-    ConnectPacket       & ConnectPacketError        => Connect,
-    ConnackPacket       & ConnackPacketError        => ConnectAcknowledgement,
+    ConnectPacket       & ConnectPacketError        => Connect                    / into_connect,
+    ConnackPacket       & ConnackPacketError        => ConnectAcknowledgement     / into_connack,
 
-    PublishPacket       & PublishPacketError        => Publish,
-    PubackPacket        & PubackPacketError         => PublishAcknowledgement,
-    PubrecPacket        & PubrecPacketError         => PublishReceived,
-    PubrelPacket        & PubrelPacketError         => PublishRelease,
-    PubcompPacket       & PubcompPacketError        => PublishComplete,
+    PublishPacket       & PublishPacketError        => Publish                    / into_publish,
+    PubackPacket        & PubackPacketError         => PublishAcknowledgement     / into_puback,
+    PubrecPacket        & PubrecPacketError         => PublishReceived            / into_pubrec,
+    PubrelPacket        & PubrelPacketError         => PublishRelease             / into_pubrel,
+    PubcompPacket       & PubcompPacketError        => PublishComplete            / into_pubcomp,
 
-    PingreqPacket       & PingreqPacketError        => PingRequest,
-    PingrespPacket      & PingrespPacketError       => PingResponse,
+    PingreqPacket       & PingreqPacketError        => PingRequest                / into_pingreq,
+    PingrespPacket      & PingrespPacketError       => PingResponse               / into_pingresp,
 
-    SubscribePacket     & SubscribePacketError      => Subscribe,
-    SubackPacket        & SubackPacketError         => SubscribeAcknowledgement,
+    SubscribePacket     & SubscribePacketError      => Subscribe                  / into_subscribe,
+    SubackPacket        & SubackPacketError         => SubscribeAcknowledgement   / into_suback,
 
-    UnsubscribePacket   & UnsubscribePacketError    => Unsubscribe,
-    UnsubackPacket      & UnsubackPacketError       => UnsubscribeAcknowledgement,
+    UnsubscribePacket   & UnsubscribePacketError    => Unsubscribe                / into_unsubscribe,
+    UnsubackPacket      & UnsubackPacketError       => UnsubscribeAcknowledgement / into_unsuback,
 
-    DisconnectPacket    & DisconnectPacketError     => Disconnect,
+    DisconnectPacket    & DisconnectPacketError     => Disconnect                 / into_disconnect,
 }
 
 impl VariablePacket {
@@ -328,17 +721,310 @@ impl VariablePacket {
     {
         From::from(t)
     }
+
+    /// Control type from the fixed header
+    pub fn control_type(&self) -> ControlType {
+        self.fixed_header().packet_type.control_type()
+    }
+
+    /// The `PUBLISH` packet, if this is one
+    pub fn as_publish(&self) -> Option<&PublishPacket> {
+        match *self {
+            VariablePacket::PublishPacket(ref pk) => Some(pk),
+            _ => None,
+        }
+    }
+
+    pub fn is_publish(&self) -> bool {
+        self.control_type() == ControlType::Publish
+    }
+
+    /// Whether this packet answers a request of the peer: `CONNACK`, `PUBACK`, `PUBREC`,
+    /// `PUBCOMP`, `SUBACK`, `UNSUBACK` or `PINGRESP`
+    ///
+    /// `PUBREL` is sent by the publisher of a QoS 2 message, so it is not an acknowledgement.
+    pub fn is_ack(&self) -> bool {
+        matches!(
+            self.control_type(),
+            ControlType::ConnectAcknowledgement
+                | ControlType::PublishAcknowledgement
+                | ControlType::PublishReceived
+                | ControlType::PublishComplete
+                | ControlType::SubscribeAcknowledgement
+                | ControlType::UnsubscribeAcknowledgement
+                | ControlType::PingResponse
+        )
+    }
+
+    /// Encodes the packet to a new `Vec`
+    pub fn encode_to_vec(&self) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(self.encoded_length() as usize);
+        self.encode(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Hex dump of the encoded packet, annotated with the fixed header and each field
+    ///
+    /// ```plain
+    /// 0000  32                                               Publish flags=0x2
+    /// 0001  0e                                               remaining_length=14
+    /// 0002  00 03 61 2f 62                                   topic_name
+    /// ...
+    /// ```
+    pub fn debug_dump(&self) -> String {
+        let buf = match self.encode_to_vec() {
+            Ok(buf) => buf,
+            Err(err) => return format!("failed to encode packet: {}", err),
+        };
+
+        let fixed_header = self.fixed_header();
+        let header_len = fixed_header.encoded_length() as usize;
+        let mut sections = vec![
+            (
+                1,
+                format!(
+                    "{:?} flags={:#x}",
+                    fixed_header.packet_type.control_type(),
+                    fixed_header.packet_type.flags()
+                ),
+            ),
+            (
+                header_len - 1,
+                format!("remaining_length={}", fixed_header.remaining_length),
+            ),
+        ];
+        sections.extend(
+            self.encoded_fields()
+                .into_iter()
+                .filter(|&(_, len)| len > 0)
+                .map(|(name, len)| (len as usize, name.to_owned())),
+        );
+
+        let mut dump = String::new();
+        let mut offset = 0;
+        for (len, annotation) in sections {
+            let end = (offset + len).min(buf.len());
+            for (i, chunk) in buf[offset..end].chunks(16).enumerate() {
+                let hex = chunk.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ");
+                let annotation = if i == 0 { &annotation[..] } else { "" };
+                dump.push_str(format!("{:04x}  {:<47}  {}", offset + i * 16, hex, annotation).trim_end());
+                dump.push('\n');
+            }
+            offset = end;
+        }
+        dump
+    }
+
+    /// Decodes a packet like `decode`, but reports errors caused by a specific byte (e.g. invalid
+    /// QoS, reserved flags or protocol version) as `VariablePacketError::AtOffset`, with the offset
+    /// of that byte from the start of the packet
+    pub fn decode_with_offsets<R: Read>(reader: &mut R) -> Result<VariablePacket, VariablePacketError> {
+        let mut reader = OffsetReader::new(reader);
+        VariablePacket::decode(&mut reader).map_err(|error| match error.invalid_byte_offset(reader.offset) {
+            Some(offset) => VariablePacketError::AtOffset {
+                offset,
+                error: Box::new(error),
+            },
+            None => error,
+        })
+    }
+
+    /// Decodes the packet at the beginning of `data`, returning it with the number of bytes it
+    /// took, for slices where the packet is followed by other data
+    pub fn decode_prefix(data: &[u8]) -> Result<(VariablePacket, usize), VariablePacketError> {
+        let mut rest = data;
+        let packet = decode_slice(&mut rest)?;
+        Ok((packet, data.len() - rest.len()))
+    }
+
+    /// Decodes all the packets encoded back to back in `data`, e.g. a captured session
+    ///
+    /// Fails if `data` ends with a partial packet.
+    pub fn decode_all(mut data: &[u8]) -> Result<Vec<VariablePacket>, VariablePacketError> {
+        let mut packets = Vec::new();
+        while !data.is_empty() {
+            packets.push(decode_slice(&mut data)?);
+        }
+        Ok(packets)
+    }
+
+    /// Decodes a packet from the beginning of `buf` without blocking, for driving the parser from
+    /// a custom read loop
+    ///
+    /// Returns `Ok(None)` and consumes nothing if `buf` does not hold a whole packet yet. Otherwise
+    /// the packet is removed from `buf`, even if decoding it fails.
+    ///
+    /// This requires mqtt-rs to be built with `feature = "bytes"`
+    #[cfg(feature = "bytes")]
+    pub fn decode_incremental(buf: &mut BytesMut) -> Result<Option<VariablePacket>, VariablePacketError> {
+        let packet_size = match packet_size(&buf[..]) {
+            Some(Ok(size)) => size,
+            Some(Err(err)) => return Err(err.into()),
+            None => return Ok(None),
+        };
+
+        if buf.len() < packet_size {
+            return Ok(None);
+        }
+        let data = buf.split_to(packet_size);
+        VariablePacket::decode(&mut &data[..]).map(Some)
+    }
+}
+
+/// Size of the whole packet at the beginning of `data`, including packets of reserved types
+///
+/// Returns `None` if `data` does not contain the complete fixed header yet.
+fn packet_size(data: &[u8]) -> Option<Result<usize, FixedHeaderError>> {
+    match FixedHeader::decode_from_slice(data)? {
+        Ok((fixed_header, header_size)) => Some(Ok(header_size + fixed_header.remaining_length as usize)),
+        Err(FixedHeaderError::ReservedType(_, length)) => {
+            // The header is complete, so it ends with the first byte without continuation bit
+            let header_size = 2 + data[1..].iter().take_while(|&&b| b & 0x80 != 0).count();
+            Some(Ok(header_size + length as usize))
+        }
+        Err(err) => Some(Err(err)),
+    }
+}
+
+/// Decodes a single packet from the beginning of `data`
+///
+/// This is a slice-based entry point for fuzzing and for input that is already framed. It never
+/// reads past the end of `data`, a packet declaring a remaining length beyond it is rejected with
+/// `VariablePacketError::IncompletePacket` before anything is allocated for its body.
+///
+/// ```rust,ignore
+/// // fuzz/fuzz_targets/decode.rs
+/// fuzz_target!(|data: &[u8]| {
+///     let _ = mqtt::packet::decode_any(data);
+/// });
+/// ```
+pub fn decode_any(data: &[u8]) -> Result<VariablePacket, VariablePacketError> {
+    decode_slice(&mut &data[..])
+}
+
+/// Like `decode_any`, but also rejects a packet larger than `max_packet_size` bytes, including the
+/// fixed header, with `VariablePacketError::PacketTooLarge`
+///
+/// This lets a fuzz target or a server apply the same limit as its network decoder.
+pub fn decode_any_with_max(data: &[u8], max_packet_size: u32) -> Result<VariablePacket, VariablePacketError> {
+    if let Some(Ok(size)) = packet_size(data) {
+        // At most 5 bytes of fixed header and `MAX_REMAINING_LENGTH`, which fits in u32
+        let size = size as u32;
+        if size > max_packet_size {
+            return Err(VariablePacketError::PacketTooLarge {
+                size,
+                max: max_packet_size,
+            });
+        }
+    }
+    decode_any(data)
+}
+
+/// Decodes a packet from the beginning of `data` like `decode_any`, and advances `data` past it
+fn decode_slice(data: &mut &[u8]) -> Result<VariablePacket, VariablePacketError> {
+    if let Some(Ok((fixed_header, header_size))) = FixedHeader::decode_from_slice(data) {
+        let available = data.len() - header_size;
+        if fixed_header.remaining_length as usize > available {
+            return Err(VariablePacketError::IncompletePacket {
+                remaining_length: fixed_header.remaining_length,
+                received: available as u32,
+            });
+        }
+    }
+    VariablePacket::decode(data)
+}
+
+/// Decodes every packet in `buf` and checks that their types are exactly `expected`, in order
+///
+/// Useful for asserting what a client wrote in integration tests.
+pub fn validate_sequence(buf: &[u8], expected: &[ControlType]) -> Result<(), SequenceError> {
+    let mut reader = buf;
+
+    for (index, &expected) in expected.iter().enumerate() {
+        if reader.is_empty() {
+            return Err(SequenceError::MissingPacket { index, expected });
+        }
+
+        let packet =
+            VariablePacket::decode(&mut reader).map_err(|error| SequenceError::DecodeError { index, error })?;
+        let found = packet.control_type();
+        if found != expected {
+            return Err(SequenceError::UnexpectedType { index, expected, found });
+        }
+    }
+
+    let remaining = reader.len();
+    if remaining > 0 {
+        return Err(SequenceError::TrailingBytes(remaining));
+    }
+    Ok(())
+}
+
+/// A packet of another type than expected, e.g. while waiting for `CONNACK`
+#[derive(Debug, Eq, PartialEq, Copy, Clone, thiserror::Error)]
+#[error("expected {expected:?}, found {found:?}")]
+pub struct UnexpectedPacket {
+    pub expected: ControlType,
+    pub found: ControlType,
+}
+
+/// Errors while validating a sequence of packets
+#[derive(Debug, thiserror::Error)]
+pub enum SequenceError {
+    #[error("packet #{index}: {error}")]
+    DecodeError { index: usize, error: VariablePacketError },
+    #[error("packet #{index}: expected {expected:?}, found {found:?}")]
+    UnexpectedType {
+        index: usize,
+        expected: ControlType,
+        found: ControlType,
+    },
+    #[error("packet #{index}: expected {expected:?}, found end of buffer")]
+    MissingPacket { index: usize, expected: ControlType },
+    #[error("{0} bytes left after the expected packets")]
+    TrailingBytes(usize),
+}
+
+/// Reader counting bytes that have been read, and whether the end of stream has been reached
+struct OffsetReader<'a, R: ?Sized> {
+    inner: &'a mut R,
+    offset: u64,
+    eof: bool,
+}
+
+impl<'a, R: ?Sized> OffsetReader<'a, R> {
+    fn new(inner: &'a mut R) -> OffsetReader<'a, R> {
+        OffsetReader {
+            inner,
+            offset: 0,
+            eof: false,
+        }
+    }
+}
+
+impl<R: Read + ?Sized> Read for OffsetReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.offset += n as u64;
+        self.eof |= n == 0 && !buf.is_empty();
+        Ok(n)
+    }
 }
 
 #[cfg(feature = "tokio-codec")]
 mod tokio_codec {
     use super::*;
-    use crate::control::packet_type::{PacketType, PacketTypeError};
+    use crate::control::fixed_header::remaining_length_size;
+    use crate::control::packet_type::PacketType;
     use bytes::{Buf, BufMut, BytesMut};
-    use tokio_util::codec;
+    use core::cmp;
+    use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+    use tokio::net::TcpStream;
+    use tokio_util::codec::{self, FramedRead, FramedWrite};
 
     pub struct MqttDecoder {
         state: DecodeState,
+        max_packet_size: Option<u32>,
     }
 
     enum DecodeState {
@@ -356,49 +1042,47 @@ mod tokio_codec {
         pub const fn new() -> Self {
             MqttDecoder {
                 state: DecodeState::Start,
+                max_packet_size: None,
             }
         }
-    }
-
-    /// Like FixedHeader::decode(), but on a buffer instead of a stream. Returns None if it reaches
-    /// the end of the buffer before it finishes decoding the header.
-    #[inline]
-    fn decode_header(mut data: &[u8]) -> Option<Result<(DecodePacketType, u32, usize), FixedHeaderError>> {
-        let mut header_size = 0;
-        macro_rules! read_u8 {
-            () => {{
-                let (&x, rest) = data.split_first()?;
-                data = rest;
-                header_size += 1;
-                x
-            }};
-        }
-
-        let type_val = read_u8!();
-        let remaining_len = {
-            let mut cur = 0u32;
-            for i in 0.. {
-                let byte = read_u8!();
-                cur |= ((byte as u32) & 0x7F) << (7 * i);
-
-                if i >= 4 {
-                    return Some(Err(FixedHeaderError::MalformedRemainingLength));
-                }
 
-                if byte & 0x80 == 0 {
-                    break;
-                }
+        /// Creates a decoder rejecting packets larger than `max` bytes, including the fixed header
+        ///
+        /// `VariablePacketError::PacketTooLarge` is returned as soon as such a fixed header is
+        /// received, before buffering its body. The stream cannot be decoded any further after that.
+        pub const fn with_max_packet_size(max: u32) -> Self {
+            MqttDecoder {
+                state: DecodeState::Start,
+                max_packet_size: Some(max),
             }
+        }
+    }
 
-            cur
-        };
+    impl Default for MqttDecoder {
+        fn default() -> Self {
+            MqttDecoder::new()
+        }
+    }
 
-        let packet_type = match PacketType::from_u8(type_val) {
-            Ok(ty) => DecodePacketType::Standard(ty),
-            Err(PacketTypeError::ReservedType(ty, _)) => DecodePacketType::Reserved(ty),
-            Err(err) => return Some(Err(err.into())),
+    /// Like `FixedHeader::decode_from_slice`, but also returns the remaining length of reserved
+    /// packet types, so that their body can be consumed. Returns None if it reaches the end of the
+    /// buffer before it finishes decoding the header.
+    #[inline]
+    fn decode_header(data: &[u8]) -> Option<Result<(DecodePacketType, u32, usize), FixedHeaderError>> {
+        let header = match FixedHeader::decode_from_slice(data)? {
+            Ok((header, size)) => (
+                DecodePacketType::Standard(header.packet_type),
+                header.remaining_length,
+                size,
+            ),
+            Err(FixedHeaderError::ReservedType(code, length)) => {
+                // The remaining length was decoded, so it was minimally encoded
+                let size = 1 + remaining_length_size(length) as usize;
+                (DecodePacketType::Reserved(code), length, size)
+            }
+            Err(err) => return Some(Err(err)),
         };
-        Some(Ok((packet_type, remaining_len, header_size)))
+        Some(Ok(header))
     }
 
     impl codec::Decoder for MqttDecoder {
@@ -409,6 +1093,12 @@ mod tokio_codec {
                 match &mut self.state {
                     DecodeState::Start => match decode_header(&src[..]) {
                         Some(Ok((typ, length, header_size))) => {
+                            if let Some(max) = self.max_packet_size {
+                                let size = header_size as u32 + length;
+                                if size > max {
+                                    return Err(VariablePacketError::PacketTooLarge { size, max });
+                                }
+                            }
                             src.advance(header_size);
                             self.state = DecodeState::Packet { length, typ };
                             continue;
@@ -431,9 +1121,13 @@ mod tokio_codec {
                                     packet_type: typ,
                                     remaining_length: length,
                                 };
-                                return decode_with_header(&mut src.reader(), header).map(Some);
+                                // Consume the whole packet even if decoding fails, to keep the stream in sync
+                                let data = src.split_to(length as usize);
+                                return decode_with_header(&mut data.reader(), header).map(Some);
                             }
                             DecodePacketType::Reserved(code) => {
+                                // The body is already buffered in `src`, so the copy is no larger than what was
+                                // received. It is only bounded by the maximum packet size if one was configured.
                                 let data = src[..length as usize].to_vec();
                                 src.advance(length as usize);
                                 return Err(VariablePacketError::ReservedPacket(code, data));
@@ -446,19 +1140,47 @@ mod tokio_codec {
     }
 
     pub struct MqttEncoder {
-        _priv: (),
+        capacity_hint: Option<usize>,
     }
 
     impl MqttEncoder {
         pub const fn new() -> Self {
-            MqttEncoder { _priv: () }
+            MqttEncoder { capacity_hint: None }
+        }
+
+        /// Creates an encoder reserving at least `hint` bytes in the destination for each packet
+        ///
+        /// The hint is raised to the largest packet encoded so far, so a stream of similarly sized
+        /// packets does not grow the buffer of a `FramedWrite` over and over.
+        pub const fn with_capacity_hint(hint: usize) -> Self {
+            MqttEncoder {
+                capacity_hint: Some(hint),
+            }
+        }
+
+        /// The number of bytes currently reserved for each packet, if a hint has been set
+        pub fn capacity_hint(&self) -> Option<usize> {
+            self.capacity_hint
+        }
+    }
+
+    impl Default for MqttEncoder {
+        fn default() -> Self {
+            MqttEncoder::new()
         }
     }
 
     impl<T: EncodablePacket> codec::Encoder<T> for MqttEncoder {
         type Error = io::Error;
         fn encode(&mut self, packet: T, dst: &mut BytesMut) -> Result<(), io::Error> {
-            dst.reserve(packet.encoded_length() as usize);
+            let length = packet.encoded_length() as usize;
+            match &mut self.capacity_hint {
+                Some(hint) => {
+                    *hint = cmp::max(*hint, length);
+                    dst.reserve(*hint);
+                }
+                None => dst.reserve(length),
+            }
             packet.encode(&mut dst.writer())
         }
     }
@@ -475,6 +1197,21 @@ mod tokio_codec {
                 encode: MqttEncoder::new(),
             }
         }
+
+        /// Creates a codec rejecting received packets larger than `max` bytes, see
+        /// [`MqttDecoder::with_max_packet_size`]
+        pub const fn with_max_packet_size(max: u32) -> Self {
+            MqttCodec {
+                decode: MqttDecoder::with_max_packet_size(max),
+                encode: MqttEncoder::new(),
+            }
+        }
+    }
+
+    impl Default for MqttCodec {
+        fn default() -> Self {
+            MqttCodec::new()
+        }
     }
 
     impl codec::Decoder for MqttCodec {
@@ -493,19 +1230,94 @@ mod tokio_codec {
             self.encode.encode(packet, dst)
         }
     }
+
+    /// Splits `stream` into a `Stream` of received packets and a `Sink` of packets to send
+    ///
+    /// ```rust,no_run
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// use futures::{SinkExt, StreamExt};
+    /// use mqtt::packet::{connect_framed, ConnectPacket, VariablePacket};
+    ///
+    /// let stream = tokio::net::TcpStream::connect("127.0.0.1:1883").await?;
+    /// let (mut packets, mut sink) = connect_framed(stream);
+    /// sink.send(VariablePacket::new(ConnectPacket::new("client"))).await?;
+    /// let connack = packets.next().await.unwrap()?.into_connack()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// This requires mqtt-rs to be built with `feature = "tokio-codec"`
+    pub fn connect_framed(
+        stream: TcpStream,
+    ) -> (
+        FramedRead<OwnedReadHalf, MqttDecoder>,
+        FramedWrite<OwnedWriteHalf, MqttEncoder>,
+    ) {
+        let (rd, wr) = stream.into_split();
+        (
+            FramedRead::new(rd, MqttDecoder::new()),
+            FramedWrite::new(wr, MqttEncoder::new()),
+        )
+    }
 }
 
 #[cfg(feature = "tokio-codec")]
-pub use tokio_codec::{MqttCodec, MqttDecoder, MqttEncoder};
+pub use tokio_codec::{connect_framed, MqttCodec, MqttDecoder, MqttEncoder};
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
 
+    use std::convert::TryInto;
     use std::io::Cursor;
 
+    use crate::control::ConnectReturnCode;
     use crate::{Decodable, Encodable};
 
+    #[test]
+    fn test_encode_into() {
+        let packet = VariablePacket::new(ConnectPacket::new("client"));
+
+        let mut buf = vec![0xFF];
+        let written = packet.encode_into(&mut buf).unwrap();
+        assert_eq!(written as u32, packet.encoded_length());
+
+        let mut expected = vec![0xFF];
+        packet.encode(&mut expected).unwrap();
+        assert_eq!(buf, expected);
+
+        // Password without a user name is rejected by `encode_packet`
+        let mut packet = ConnectPacket::new("client");
+        packet.set_password(Some("secret".to_owned()));
+        let mut buf = vec![0xFF];
+        let err = packet.encode_into(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(buf, [0xFF]);
+    }
+
+    #[test]
+    fn test_encode_to_slice() {
+        let mut buf = [0u8; 4];
+        assert_eq!(PubackPacket::new(10).encode_to_slice(&mut buf).unwrap(), 4);
+        assert_eq!(buf, *b"\x40\x02\x00\x0A");
+
+        let mut buf = [0u8; 8];
+        assert_eq!(PingreqPacket::new().encode_to_slice(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"\xC0\x00");
+
+        let mut buf = [0u8; 3];
+        let err = PubackPacket::new(10).encode_to_slice(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+        assert_eq!(
+            err.get_ref().and_then(|err| err.downcast_ref::<BufferTooSmall>()),
+            Some(&BufferTooSmall {
+                required: 4,
+                available: 3
+            })
+        );
+        assert_eq!(buf, [0; 3]);
+    }
+
     #[test]
     fn test_variable_packet_basic() {
         let packet = ConnectPacket::new("1234".to_owned());
@@ -524,6 +1336,541 @@ mod test {
         assert_eq!(var_packet, decoded_packet);
     }
 
+    #[test]
+    fn test_variable_packet_into() {
+        let packet = VariablePacket::new(ConnackPacket::new(false, ConnectReturnCode::ConnectionAccepted));
+        assert_eq!(packet.control_type(), ControlType::ConnectAcknowledgement);
+        assert_eq!(
+            packet.clone().into_suback(),
+            Err(UnexpectedPacket {
+                expected: ControlType::SubscribeAcknowledgement,
+                found: ControlType::ConnectAcknowledgement,
+            })
+        );
+        let packet = SubackPacket::try_from(packet).unwrap_err();
+        let connack: ConnackPacket = packet.try_into().unwrap();
+        assert_eq!(connack.connect_return_code(), ConnectReturnCode::ConnectionAccepted);
+
+        let connack = VariablePacket::new(connack).into_connack().unwrap();
+        assert_eq!(connack.connect_return_code(), ConnectReturnCode::ConnectionAccepted);
+    }
+
+    #[test]
+    fn test_variable_packet_decode_all() {
+        let packets = vec![
+            VariablePacket::new(PingreqPacket::new()),
+            VariablePacket::new(PubackPacket::new(10)),
+            VariablePacket::new(PublishPacket::new(
+                crate::TopicName::new("a/b").unwrap(),
+                QoSWithPacketIdentifier::Level0,
+                "x",
+            )),
+        ];
+        let mut buf = Vec::new();
+        for packet in &packets {
+            packet.encode(&mut buf).unwrap();
+        }
+        assert_eq!(VariablePacket::decode_all(&buf).unwrap(), packets);
+        assert_eq!(VariablePacket::decode_all(&[]).unwrap(), []);
+
+        assert!(matches!(
+            VariablePacket::decode_all(&buf[..buf.len() - 1]),
+            Err(VariablePacketError::IncompletePacket {
+                remaining_length: 6,
+                received: 5,
+            })
+        ));
+        assert!(matches!(
+            VariablePacket::decode_all(&buf[..3]),
+            Err(VariablePacketError::FixedHeaderError(FixedHeaderError::IoError(..)))
+        ));
+    }
+
+    #[test]
+    fn test_variable_packet_decode_prefix() {
+        let mut buf = Vec::new();
+        PubackPacket::new(10).encode(&mut buf).unwrap();
+        PingreqPacket::new().encode(&mut buf).unwrap();
+
+        let (packet, size) = VariablePacket::decode_prefix(&buf).unwrap();
+        assert_eq!(packet, VariablePacket::new(PubackPacket::new(10)));
+        assert_eq!(size, 4);
+        let (packet, size) = VariablePacket::decode_prefix(&buf[size..]).unwrap();
+        assert_eq!(packet, VariablePacket::new(PingreqPacket::new()));
+        assert_eq!(size, 2);
+
+        // Trailing bytes that are not a packet are left alone
+        let mut data = buf[..4].to_vec();
+        data.extend_from_slice(b"\xFF\xFF");
+        assert_eq!(
+            VariablePacket::decode_prefix(&data).unwrap(),
+            (VariablePacket::new(PubackPacket::new(10)), 4)
+        );
+
+        assert!(matches!(
+            VariablePacket::decode_prefix(&buf[..3]),
+            Err(VariablePacketError::IncompletePacket {
+                remaining_length: 2,
+                received: 1,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_variable_packet_as_publish() {
+        let mut publish = PublishPacket::new(
+            crate::TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level0,
+            "x",
+        );
+        publish.set_retain(true);
+        let packet = VariablePacket::new(publish);
+        assert!(packet.is_publish() && !packet.is_ack());
+        assert!(packet.as_publish().unwrap().retain());
+
+        let packet = VariablePacket::new(PubrecPacket::new(10));
+        assert!(!packet.is_publish() && packet.is_ack());
+        assert_eq!(packet.as_publish(), None);
+        assert!(!VariablePacket::new(PubrelPacket::new(10)).is_ack());
+        assert!(VariablePacket::new(PingrespPacket::new()).is_ack());
+    }
+
+    #[test]
+    fn test_variable_packet_hash() {
+        use std::collections::HashSet;
+
+        let publish = |pkid, dup| {
+            let mut packet = PublishPacket::new(
+                crate::TopicName::new("a/b").unwrap(),
+                QoSWithPacketIdentifier::Level1(pkid),
+                "x",
+            );
+            packet.set_dup(dup);
+            VariablePacket::from(packet)
+        };
+
+        let mut seen = HashSet::new();
+        assert!(seen.insert(publish(10, false)));
+        assert!(!seen.insert(publish(10, false)));
+        assert!(seen.insert(publish(10, true)));
+        assert!(seen.insert(publish(11, false)));
+        assert!(seen.insert(PubackPacket::new(10).into()));
+        assert_eq!(seen.len(), 4);
+    }
+
+    #[test]
+    fn test_decode_length_mismatch() {
+        // PUBACK with a remaining length of 3, but only 2 bytes of packet identifier
+        let mut buf = Cursor::new(&b"\x40\x03\x00\x0A\xFF"[..]);
+        match VariablePacket::decode(&mut buf) {
+            Err(VariablePacketError::PubackPacketError(PacketError::LengthMismatch { declared, consumed })) => {
+                assert_eq!(declared, 3);
+                assert_eq!(consumed, 2);
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        // PUBLISH with a remaining length of 12, but only 10 bytes left in the stream
+        let mut buf = Cursor::new(&b"\x30\x0C\x00\x03a/bhello"[..]);
+        match PublishPacket::decode(&mut buf) {
+            Err(PacketError::LengthMismatch { declared, consumed }) => {
+                assert_eq!(declared, 12);
+                assert_eq!(consumed, 10);
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_decode_incomplete_packet() {
+        // PUBLISH with a remaining length of 12, but the stream ends after 10 bytes
+        let mut buf = Cursor::new(&b"\x30\x0C\x00\x03a/bhello"[..]);
+        match VariablePacket::decode(&mut buf) {
+            Err(VariablePacketError::IncompletePacket {
+                remaining_length,
+                received,
+            }) => {
+                assert_eq!(remaining_length, 12);
+                assert_eq!(received, 10);
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        // SUBACK ending in the middle of the packet identifier
+        let mut buf = Cursor::new(&b"\x90\x03\x00"[..]);
+        assert!(matches!(
+            VariablePacket::decode(&mut buf),
+            Err(VariablePacketError::IncompletePacket {
+                remaining_length: 3,
+                received: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn test_decode_zero_packet_identifier() {
+        // PUBLISH QoS 1, PUBACK, SUBACK, UNSUBSCRIBE with packet identifier 0
+        let packets: [&[u8]; 4] = [
+            b"\x32\x07\x00\x03a/b\x00\x00",
+            b"\x40\x02\x00\x00",
+            b"\x90\x03\x00\x00\x00",
+            b"\xA2\x05\x00\x00\x00\x01a",
+        ];
+        for packet in packets.iter() {
+            let mut buf = Cursor::new(*packet);
+            assert!(VariablePacket::decode(&mut buf).is_err(), "{:?}", packet);
+        }
+
+        // PUBLISH QoS 0 has no packet identifier at all
+        let mut buf = Cursor::new(&b"\x30\x05\x00\x03a/b"[..]);
+        VariablePacket::decode(&mut buf).unwrap();
+    }
+
+    #[test]
+    fn test_decode_with_offsets() {
+        // SUBSCRIBE with an invalid QoS byte at the end
+        let mut buf = Cursor::new(&b"\x82\x06\x00\x01\x00\x01a\x03"[..]);
+        match VariablePacket::decode_with_offsets(&mut buf) {
+            Err(VariablePacketError::AtOffset { offset, error }) => {
+                assert_eq!(offset, 7);
+                assert!(matches!(
+                    *error,
+                    VariablePacketError::SubscribePacketError(PacketError::PayloadError(
+                        SubscribePacketError::InvalidQualityOfService
+                    ))
+                ));
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        // PUBLISH with QoS 3 in the flags of the first byte
+        let mut buf = Cursor::new(&b"\x36\x07\x00\x03a/b\x00\x01"[..]);
+        match VariablePacket::decode_with_offsets(&mut buf) {
+            Err(VariablePacketError::AtOffset { offset, error }) => {
+                assert_eq!(offset, 0);
+                assert!(matches!(
+                    *error,
+                    VariablePacketError::FixedHeaderError(FixedHeaderError::PacketTypeError(..))
+                ));
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        // PUBLISH QoS 1 without a packet identifier
+        let mut buf = Cursor::new(&b"\x32\x05\x00\x03a/b"[..]);
+        assert!(matches!(
+            VariablePacket::decode_with_offsets(&mut buf),
+            Err(VariablePacketError::PublishPacketError(PacketError::PayloadError(..)))
+        ));
+
+        // CONNECT with an invalid protocol level
+        let mut buf = Cursor::new(&b"\x10\x11\x00\x04MQTT\x07\x00\x00\x00\x00\x0512345"[..]);
+        match VariablePacket::decode_with_offsets(&mut buf) {
+            Err(VariablePacketError::AtOffset { offset, .. }) => assert_eq!(offset, 8),
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        // Not caused by a specific byte
+        let mut buf = Cursor::new(&b"\x40\x03\x00\x0A\xFF"[..]);
+        assert!(matches!(
+            VariablePacket::decode_with_offsets(&mut buf),
+            Err(VariablePacketError::PubackPacketError(
+                PacketError::LengthMismatch { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_variable_packet_display() {
+        use crate::{QualityOfService, TopicFilter, TopicName};
+
+        let packet = VariablePacket::new(PublishPacket::new(
+            TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level1(10),
+            b"Hello world!".to_vec(),
+        ));
+        assert_eq!(
+            packet.to_string(),
+            "PUBLISH topic=a/b qos=1 id=10 retain=false dup=false len=12 payload=\"Hello world!\""
+        );
+
+        let packet = VariablePacket::new(PublishPacket::new(
+            TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level0,
+            vec![b'x'; 100],
+        ));
+        assert_eq!(
+            packet.to_string(),
+            format!(
+                "PUBLISH topic=a/b qos=0 retain=false dup=false len=100 payload=\"{}\"",
+                "x".repeat(32)
+            )
+        );
+
+        let packet = VariablePacket::new(SubscribePacket::new(
+            10,
+            vec![
+                (TopicFilter::new("sport/#").unwrap(), QualityOfService::Level1),
+                (TopicFilter::new("a/+").unwrap(), QualityOfService::Level0),
+            ],
+        ));
+        assert_eq!(packet.to_string(), "SUBSCRIBE id=10 [sport/#:QoS1, a/+:QoS0]");
+
+        let packet = VariablePacket::new(ConnectPacket::new("1234"));
+        assert_eq!(
+            packet.to_string(),
+            "CONNECT client_id=1234 protocol=MQTT level=4 clean_session=false keep_alive=0"
+        );
+
+        assert_eq!(VariablePacket::new(PubackPacket::new(10)).to_string(), "PUBACK id=10");
+        assert_eq!(VariablePacket::new(PingreqPacket::new()).to_string(), "PINGREQ");
+    }
+
+    #[test]
+    fn test_validate_sequence() {
+        use crate::{QualityOfService, TopicFilter};
+
+        let mut buf = Vec::new();
+        ConnectPacket::new("1234").encode(&mut buf).unwrap();
+        SubscribePacket::new(10, vec![(TopicFilter::new("a/b").unwrap(), QualityOfService::Level0)])
+            .encode(&mut buf)
+            .unwrap();
+
+        validate_sequence(&buf, &[ControlType::Connect, ControlType::Subscribe]).unwrap();
+
+        match validate_sequence(&buf, &[ControlType::Connect, ControlType::Publish]) {
+            Err(SequenceError::UnexpectedType { index, expected, found }) => {
+                assert_eq!(index, 1);
+                assert_eq!(expected, ControlType::Publish);
+                assert_eq!(found, ControlType::Subscribe);
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        assert!(matches!(
+            validate_sequence(
+                &buf,
+                &[ControlType::Connect, ControlType::Subscribe, ControlType::PingRequest]
+            ),
+            Err(SequenceError::MissingPacket { index: 2, .. })
+        ));
+        assert!(matches!(
+            validate_sequence(&buf, &[ControlType::Connect]),
+            Err(SequenceError::TrailingBytes(..))
+        ));
+        assert!(matches!(
+            validate_sequence(&buf[..buf.len() - 1], &[ControlType::Connect, ControlType::Subscribe]),
+            Err(SequenceError::DecodeError { index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_decode_any() {
+        use crate::{QualityOfService, TopicFilter, TopicName};
+
+        let packets = [
+            VariablePacket::new(ConnectPacket::new("1234")),
+            VariablePacket::new(PublishPacket::new(
+                TopicName::new("a/b").unwrap(),
+                QoSWithPacketIdentifier::Level2(10),
+                b"hello".to_vec(),
+            )),
+            VariablePacket::new(SubscribePacket::new(
+                10,
+                vec![(TopicFilter::new("a/+").unwrap(), QualityOfService::Level1)],
+            )),
+            VariablePacket::new(PingreqPacket::new()),
+        ];
+
+        for packet in &packets {
+            let buf = packet.encode_to_vec().unwrap();
+            assert_eq!(&decode_any(&buf).unwrap(), packet);
+
+            for len in 0..buf.len() {
+                assert!(decode_any(&buf[..len]).is_err());
+            }
+        }
+
+        // A huge remaining length is rejected upfront
+        assert!(matches!(
+            decode_any(b"\x30\xff\xff\xff\x7f\x00\x01a"),
+            Err(VariablePacketError::IncompletePacket {
+                remaining_length: MAX_REMAINING_LENGTH,
+                received: 3
+            })
+        ));
+
+        // The maximum packet size includes the fixed header
+        let buf = b"\x30\x06\x00\x03a/bx";
+        assert!(decode_any_with_max(buf, 8).is_ok());
+        assert!(matches!(
+            decode_any_with_max(buf, 7),
+            Err(VariablePacketError::PacketTooLarge { size: 8, max: 7 })
+        ));
+        assert!(matches!(
+            decode_any_with_max(b"\x30\xff\xff\xff\x7f\x00\x01a", 1024),
+            Err(VariablePacketError::PacketTooLarge { max: 1024, .. })
+        ));
+    }
+
+    #[test]
+    fn test_variable_packet_debug_dump() {
+        use crate::TopicName;
+
+        let packet = VariablePacket::new(PublishPacket::new(
+            TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level1(10),
+            b"Hello world!".to_vec(),
+        ));
+
+        let buf = packet.encode_to_vec().unwrap();
+        let mut expected = Vec::new();
+        packet.encode(&mut expected).unwrap();
+        assert_eq!(buf, expected);
+
+        let dump = packet.debug_dump();
+        let lines = dump.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("0000  32 "));
+        assert!(lines[0].ends_with("Publish flags=0x2"));
+        assert!(lines[1].starts_with("0001  13 "));
+        assert!(lines[1].ends_with("remaining_length=19"));
+        assert!(lines[2].starts_with("0002  00 03 61 2f 62 "));
+        assert!(lines[2].ends_with("topic_name"));
+        assert!(lines[3].starts_with("0007  00 0a "));
+        assert!(lines[3].ends_with("packet_identifier"));
+        assert!(lines[4].starts_with("0009  48 65 6c 6c 6f "));
+        assert!(lines[4].ends_with("payload"));
+    }
+
+    #[test]
+    fn test_encode_oversized_packet() {
+        use crate::control::PacketType;
+
+        struct OversizedPacket(FixedHeader);
+
+        impl EncodablePacket for OversizedPacket {
+            fn fixed_header(&self) -> &FixedHeader {
+                &self.0
+            }
+
+            fn encoded_packet_length(&self) -> u32 {
+                MAX_REMAINING_LENGTH + 1
+            }
+        }
+
+        let packet = OversizedPacket(FixedHeader::new(PacketType::with_default(ControlType::PingRequest), 0));
+        assert!(packet.try_encoded_length().is_err());
+
+        let mut buf = Vec::new();
+        let err = packet.encode(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(buf.is_empty());
+
+        let packet = PingreqPacket::new();
+        assert_eq!(packet.try_encoded_length().unwrap(), 2);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_variable_packet_serde() {
+        use crate::control::PacketType;
+        use crate::{QualityOfService, TopicFilter, TopicName};
+
+        let packets = vec![
+            VariablePacket::new(ConnectPacket::new("1234".to_owned())),
+            VariablePacket::new(PublishPacket::new(
+                TopicName::new("a/b").unwrap(),
+                QoSWithPacketIdentifier::Level1(10),
+                b"Hello world!".to_vec(),
+            )),
+            VariablePacket::new(SubscribePacket::new(
+                10,
+                vec![(TopicFilter::new("sport/#").unwrap(), QualityOfService::Level1)],
+            )),
+            VariablePacket::new(PingreqPacket::new()),
+        ];
+
+        for packet in packets {
+            let json = serde_json::to_string(&packet).unwrap();
+            let decoded: VariablePacket = serde_json::from_str(&json).unwrap();
+            assert_eq!(packet, decoded);
+        }
+
+        // Topic names are validated
+        assert!(serde_json::from_str::<TopicName>("\"a/#\"").is_err());
+
+        // The remaining length is computed again, as it could not be encoded otherwise
+        let packet = PublishPacket::new(
+            TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level1(10),
+            b"Hello world!".to_vec(),
+        );
+        let mut json = serde_json::to_value(&packet).unwrap();
+        json["fixed_header"]["remaining_length"] = 2.into();
+        let decoded: PublishPacket = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, packet);
+        let mut buf = Vec::new();
+        decoded.encode(&mut buf).unwrap();
+        assert_eq!(PublishPacket::decode(&mut &buf[..]).unwrap(), packet);
+
+        // The packet type must match the packet
+        let mut json = serde_json::to_value(&packet).unwrap();
+        json["fixed_header"]["packet_type"] = 0x40.into();
+        assert!(serde_json::from_value::<PublishPacket>(json).is_err());
+
+        // AUTH is MQTT 5 only
+        assert!(serde_json::from_str::<PacketType>("240").is_err());
+        assert_eq!(
+            serde_json::from_str::<PacketType>("192").unwrap(),
+            PacketType::with_default(ControlType::PingRequest)
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_variable_packet_async_parse_buffered() {
+        let packets = [
+            VariablePacket::new(ConnectPacket::new("1234")),
+            VariablePacket::new(PingreqPacket::new()),
+        ];
+        let mut buf = Vec::new();
+        for packet in packets.iter() {
+            packet.encode(&mut buf).unwrap();
+        }
+
+        // Small buffer, so the first packet does not fit and is read into a Vec
+        let mut rdr = tokio::io::BufReader::with_capacity(8, &buf[..]);
+        for packet in packets.iter() {
+            assert_eq!(&VariablePacket::parse_buffered(&mut rdr).await.unwrap(), packet);
+        }
+
+        let mut rdr = tokio::io::BufReader::new(&buf[..]);
+        for packet in packets.iter() {
+            assert_eq!(&VariablePacket::parse_buffered(&mut rdr).await.unwrap(), packet);
+        }
+        assert!(VariablePacket::parse_buffered(&mut rdr).await.is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_variable_packet_async_parse_buffered_empty_body() {
+        use std::time::Duration;
+        use tokio::io::AsyncWriteExt;
+
+        // The writer stays open, so nothing follows the PINGRESP
+        let (mut writer, reader) = tokio::io::duplex(64);
+        writer.write_all(b"\xD0\x00").await.unwrap();
+
+        let mut rdr = tokio::io::BufReader::new(reader);
+        let packet = tokio::time::timeout(Duration::from_secs(2), VariablePacket::parse_buffered(&mut rdr))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(packet, VariablePacket::new(PingrespPacket::new()));
+        drop(writer);
+    }
+
     #[cfg(feature = "tokio")]
     #[tokio::test]
     async fn test_variable_packet_async_parse() {
@@ -543,6 +1890,248 @@ mod test {
         assert_eq!(var_packet, decoded_packet);
     }
 
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_variable_packet_parse_into() {
+        use crate::TopicName;
+
+        let packets = vec![
+            VariablePacket::new(PublishPacket::new(
+                TopicName::new("a/b").unwrap(),
+                QoSWithPacketIdentifier::Level0,
+                vec![0xAA; 100],
+            )),
+            VariablePacket::new(PubackPacket::new(10)),
+        ];
+        let mut buf = Vec::new();
+        for packet in &packets {
+            packet.encode(&mut buf).unwrap();
+        }
+
+        let mut rdr = &buf[..];
+        let mut scratch = Vec::new();
+        let decoded = VariablePacket::parse_into(&mut rdr, &mut scratch).await.unwrap();
+        assert_eq!(decoded, packets[0]);
+        let capacity = scratch.capacity();
+        assert!(capacity >= 105);
+
+        let decoded = VariablePacket::parse_into(&mut rdr, &mut scratch).await.unwrap();
+        assert_eq!(decoded, packets[1]);
+        assert_eq!(scratch, [0x00, 0x0A]);
+        assert_eq!(scratch.capacity(), capacity);
+        drop(scratch);
+        assert_eq!(decoded, packets[1]);
+
+        let mut rdr = &buf[..50];
+        assert!(matches!(
+            VariablePacket::parse_into(&mut rdr, &mut Vec::new()).await,
+            Err(VariablePacketError::IncompletePacket { received: 48, .. })
+        ));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_variable_packet_parse_with_deadline() {
+        use std::time::Duration;
+        use tokio::io::AsyncWriteExt;
+        use tokio::time::Instant;
+
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_all(b"\xC0\x00\xC0").await.unwrap();
+
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let packet = VariablePacket::parse_with_deadline(&mut server, deadline)
+            .await
+            .unwrap();
+        assert_eq!(packet, VariablePacket::new(PingreqPacket::new()));
+
+        // Only the first byte of the next packet arrives
+        let deadline = Instant::now() + Duration::from_millis(50);
+        assert!(matches!(
+            VariablePacket::parse_with_deadline(&mut server, deadline).await,
+            Err(VariablePacketError::Timeout)
+        ));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_variable_packet_async_write() {
+        use crate::TopicName;
+
+        let packets = vec![
+            VariablePacket::new(ConnectPacket::new("1234".to_owned())),
+            VariablePacket::new(PublishPacket::new(
+                TopicName::new("a/b").unwrap(),
+                QoSWithPacketIdentifier::Level1(10),
+                b"Hello world!".to_vec(),
+            )),
+        ];
+
+        for var_packet in packets {
+            let mut expected = Vec::new();
+            var_packet.encode(&mut expected).unwrap();
+
+            let mut buf = Vec::new();
+            var_packet.write_to(&mut buf).await.unwrap();
+
+            assert_eq!(expected, buf);
+        }
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_variable_packet_decode_incremental() {
+        use crate::TopicName;
+
+        let packet = VariablePacket::new(PublishPacket::new(
+            TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level1(10),
+            b"hello".to_vec(),
+        ));
+        let encoded = packet.encode_to_vec().unwrap();
+
+        let mut buf = BytesMut::new();
+        for &byte in &encoded {
+            assert_eq!(VariablePacket::decode_incremental(&mut buf).unwrap(), None);
+            buf.extend_from_slice(&[byte]);
+        }
+        buf.extend_from_slice(b"\xc0");
+
+        assert_eq!(VariablePacket::decode_incremental(&mut buf).unwrap(), Some(packet));
+        assert_eq!(&buf[..], b"\xc0");
+
+        // Reserved packet types are consumed as a whole
+        let mut buf = BytesMut::from(&b"\xf0\x02\xab\xcd\xc0\x00"[..]);
+        match VariablePacket::decode_incremental(&mut buf) {
+            Err(VariablePacketError::ReservedPacket(15, data)) => assert_eq!(data, [0xab, 0xcd]),
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert_eq!(
+            VariablePacket::decode_incremental(&mut buf).unwrap(),
+            Some(VariablePacket::new(PingreqPacket::new()))
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[cfg(feature = "tokio-codec")]
+    #[test]
+    fn test_codec_default() {
+        use bytes::BytesMut;
+        use tokio_util::codec::{Decoder, Encoder};
+
+        let packet = VariablePacket::new(ConnectPacket::new("1234".to_owned()));
+
+        let mut buf = BytesMut::new();
+        MqttEncoder::default().encode(packet.clone(), &mut buf).unwrap();
+        assert_eq!(
+            MqttDecoder::default().decode(&mut buf.clone()).unwrap(),
+            Some(packet.clone())
+        );
+
+        let mut codec = MqttCodec::default();
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(packet));
+        assert!(buf.is_empty());
+    }
+
+    #[cfg(feature = "tokio-codec")]
+    #[test]
+    fn test_codec_encode_ref() {
+        use bytes::BytesMut;
+        use tokio_util::codec::Encoder;
+
+        let packet = PingreqPacket::new();
+        let mut buf = BytesMut::new();
+        let mut encoder = MqttEncoder::new();
+        encoder.encode(&packet, &mut buf).unwrap();
+        encoder.encode(&packet, &mut buf).unwrap();
+        assert_eq!(&buf[..], b"\xC0\x00\xC0\x00");
+    }
+
+    #[cfg(feature = "tokio-codec")]
+    #[test]
+    fn test_codec_capacity_hint() {
+        use bytes::BytesMut;
+        use tokio_util::codec::Encoder;
+
+        let mut encoder = MqttEncoder::with_capacity_hint(64);
+        let mut buf = BytesMut::new();
+        encoder.encode(PingreqPacket::new(), &mut buf).unwrap();
+        assert!(buf.capacity() >= 64);
+        assert_eq!(encoder.capacity_hint(), Some(64));
+
+        let publish = PublishPacket::new(
+            crate::TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level0,
+            vec![0; 200],
+        );
+        encoder.encode(&publish, &mut buf).unwrap();
+        assert_eq!(encoder.capacity_hint(), Some(publish.encoded_length() as usize));
+        assert_eq!(buf.len(), 2 + publish.encoded_length() as usize);
+
+        assert_eq!(MqttEncoder::new().capacity_hint(), None);
+    }
+
+    #[cfg(feature = "tokio-codec")]
+    #[test]
+    fn test_codec_max_packet_size() {
+        use bytes::BytesMut;
+        use tokio_util::codec::Decoder;
+
+        // Only the header of a 1MB PUBLISH has been received
+        let mut buf = BytesMut::from(&b"\x30\x80\x80\x40"[..]);
+        match MqttCodec::with_max_packet_size(1024).decode(&mut buf) {
+            Err(VariablePacketError::PacketTooLarge { size, max: 1024 }) => assert_eq!(size, 4 + 1024 * 1024),
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        let mut buf = BytesMut::from(&b"\xC0\x00"[..]);
+        assert_eq!(
+            MqttDecoder::with_max_packet_size(2).decode(&mut buf).unwrap(),
+            Some(VariablePacket::new(PingreqPacket::new()))
+        );
+
+        // Reserved types are bounded too, before their body is buffered
+        let mut buf = BytesMut::from(&b"\x00\xFF\xFF\xFF\x7F"[..]);
+        assert!(matches!(
+            MqttDecoder::with_max_packet_size(1024).decode(&mut buf),
+            Err(VariablePacketError::PacketTooLarge { .. })
+        ));
+    }
+
+    #[cfg(feature = "tokio-codec")]
+    #[test]
+    fn test_codec_non_minimal_remaining_length() {
+        use bytes::BytesMut;
+        use tokio_util::codec::Decoder;
+
+        // PINGREQ with a remaining length of 0 encoded on two bytes
+        let mut buf = BytesMut::from(&b"\xC0\x80\x00"[..]);
+        assert!(matches!(
+            MqttDecoder::new().decode(&mut buf),
+            Err(VariablePacketError::FixedHeaderError(
+                FixedHeaderError::MalformedRemainingLength
+            ))
+        ));
+
+        // The header is not complete yet
+        let mut buf = BytesMut::from(&b"\xC0\x80"[..]);
+        assert_eq!(MqttDecoder::new().decode(&mut buf).unwrap(), None);
+
+        // Reserved types are skipped with their body
+        let mut buf = BytesMut::from(&b"\x00\x82\x01"[..]);
+        buf.extend_from_slice(&[0xAB; 130]);
+        buf.extend_from_slice(b"\xC0\x00");
+        let mut decoder = MqttDecoder::new();
+        match decoder.decode(&mut buf) {
+            Err(VariablePacketError::ReservedPacket(0, data)) => assert_eq!(data, [0xAB; 130]),
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap(),
+            Some(VariablePacket::new(PingreqPacket::new()))
+        );
+    }
+
     #[cfg(feature = "tokio-codec")]
     #[tokio::test]
     async fn test_variable_packet_framed() {
@@ -577,4 +2166,35 @@ mod test {
         assert_eq!(decoded_conn, conn_packet.into());
         assert_eq!(decoded_sub, sub_packet.into());
     }
+
+    #[cfg(feature = "tokio-codec")]
+    #[tokio::test]
+    async fn test_connect_framed() {
+        use futures::{SinkExt, StreamExt};
+        use tokio::net::{TcpListener, TcpStream};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (client, server) = tokio::join!(TcpStream::connect(addr), listener.accept());
+
+        let (mut client_packets, mut client_sink) = connect_framed(client.unwrap());
+        let (mut server_packets, mut server_sink) = connect_framed(server.unwrap().0);
+
+        client_sink
+            .send(VariablePacket::new(ConnectPacket::new("1234")))
+            .await
+            .unwrap();
+        let connect = server_packets.next().await.unwrap().unwrap().into_connect().unwrap();
+        assert_eq!(connect.client_identifier(), "1234");
+
+        server_sink
+            .send(ConnackPacket::new(false, ConnectReturnCode::ConnectionAccepted))
+            .await
+            .unwrap();
+        let connack = client_packets.next().await.unwrap().unwrap().into_connack().unwrap();
+        assert_eq!(connack.connect_return_code(), ConnectReturnCode::ConnectionAccepted);
+
+        drop(client_sink);
+        assert!(server_packets.next().await.is_none());
+    }
 }
@@ -1,17 +1,24 @@
 //! PINGREQ
 
-use std::io::Read;
+use core::fmt;
 
 use crate::control::{ControlType, FixedHeader, PacketType};
+use crate::io::Read;
 use crate::packet::{DecodablePacket, PacketError};
 
 /// `PINGREQ` packet
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(remote = "Self")
+)]
 pub struct PingreqPacket {
     fixed_header: FixedHeader,
 }
 
 encodable_packet!(PingreqPacket());
+serde_packet!(PingreqPacket, PingRequest);
 
 impl PingreqPacket {
     pub fn new() -> PingreqPacket {
@@ -27,10 +34,44 @@ impl Default for PingreqPacket {
     }
 }
 
+impl fmt::Display for PingreqPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PINGREQ")
+    }
+}
+
 impl DecodablePacket for PingreqPacket {
-    type DecodePacketError = std::convert::Infallible;
+    type DecodePacketError = core::convert::Infallible;
 
     fn decode_packet<R: Read>(_reader: &mut R, fixed_header: FixedHeader) -> Result<Self, PacketError<Self>> {
+        // PINGREQ has neither variable header nor payload
+        if fixed_header.remaining_length != 0 {
+            return Err(PacketError::LengthMismatch {
+                declared: fixed_header.remaining_length,
+                consumed: 0,
+            });
+        }
         Ok(PingreqPacket { fixed_header })
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use crate::Decodable;
+
+    #[test]
+    fn test_pingreq_packet_non_empty_body() {
+        let mut buf = Cursor::new(&b"\xc0\x02\x00\x00"[..]);
+        match PingreqPacket::decode(&mut buf) {
+            Err(PacketError::LengthMismatch {
+                declared: 2,
+                consumed: 0,
+            }) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+}
@@ -1,17 +1,24 @@
 //! PINGRESP
 
-use std::io::Read;
+use core::fmt;
 
 use crate::control::{ControlType, FixedHeader, PacketType};
+use crate::io::Read;
 use crate::packet::{DecodablePacket, PacketError};
 
 /// `PINGRESP` packet
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(remote = "Self")
+)]
 pub struct PingrespPacket {
     fixed_header: FixedHeader,
 }
 
 encodable_packet!(PingrespPacket());
+serde_packet!(PingrespPacket, PingResponse);
 
 impl PingrespPacket {
     pub fn new() -> PingrespPacket {
@@ -27,10 +34,23 @@ impl Default for PingrespPacket {
     }
 }
 
+impl fmt::Display for PingrespPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PINGRESP")
+    }
+}
+
 impl DecodablePacket for PingrespPacket {
-    type DecodePacketError = std::convert::Infallible;
+    type DecodePacketError = core::convert::Infallible;
 
     fn decode_packet<R: Read>(_reader: &mut R, fixed_header: FixedHeader) -> Result<Self, PacketError<Self>> {
+        // PINGRESP has neither variable header nor payload
+        if fixed_header.remaining_length != 0 {
+            return Err(PacketError::LengthMismatch {
+                declared: fixed_header.remaining_length,
+                consumed: 0,
+            });
+        }
         Ok(PingrespPacket { fixed_header })
     }
 }
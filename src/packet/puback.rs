@@ -1,20 +1,27 @@
 //! PUBACK
 
-use std::io::Read;
+use core::fmt;
 
 use crate::control::variable_header::PacketIdentifier;
 use crate::control::{ControlType, FixedHeader, PacketType};
+use crate::io::Read;
 use crate::packet::{DecodablePacket, PacketError};
 use crate::Decodable;
 
 /// `PUBACK` packet
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(remote = "Self")
+)]
 pub struct PubackPacket {
     fixed_header: FixedHeader,
     packet_identifier: PacketIdentifier,
 }
 
 encodable_packet!(PubackPacket(packet_identifier));
+serde_packet!(PubackPacket, PublishAcknowledgement);
 
 impl PubackPacket {
     pub fn new(pkid: u16) -> PubackPacket {
@@ -33,8 +40,14 @@ impl PubackPacket {
     }
 }
 
+impl fmt::Display for PubackPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PUBACK id={}", self.packet_identifier.0)
+    }
+}
+
 impl DecodablePacket for PubackPacket {
-    type DecodePacketError = std::convert::Infallible;
+    type DecodePacketError = core::convert::Infallible;
 
     fn decode_packet<R: Read>(reader: &mut R, fixed_header: FixedHeader) -> Result<Self, PacketError<Self>> {
         let packet_identifier: PacketIdentifier = PacketIdentifier::decode(reader)?;
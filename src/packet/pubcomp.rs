@@ -1,20 +1,27 @@
 //! PUBCOMP
 
-use std::io::Read;
+use core::fmt;
 
 use crate::control::variable_header::PacketIdentifier;
 use crate::control::{ControlType, FixedHeader, PacketType};
+use crate::io::Read;
 use crate::packet::{DecodablePacket, PacketError};
 use crate::Decodable;
 
 /// `PUBCOMP` packet
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(remote = "Self")
+)]
 pub struct PubcompPacket {
     fixed_header: FixedHeader,
     packet_identifier: PacketIdentifier,
 }
 
 encodable_packet!(PubcompPacket(packet_identifier));
+serde_packet!(PubcompPacket, PublishComplete);
 
 impl PubcompPacket {
     pub fn new(pkid: u16) -> PubcompPacket {
@@ -33,8 +40,14 @@ impl PubcompPacket {
     }
 }
 
+impl fmt::Display for PubcompPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PUBCOMP id={}", self.packet_identifier.0)
+    }
+}
+
 impl DecodablePacket for PubcompPacket {
-    type DecodePacketError = std::convert::Infallible;
+    type DecodePacketError = core::convert::Infallible;
 
     fn decode_packet<R: Read>(reader: &mut R, fixed_header: FixedHeader) -> Result<Self, PacketError<Self>> {
         let packet_identifier: PacketIdentifier = PacketIdentifier::decode(reader)?;
@@ -1,9 +1,16 @@
 //! PUBLISH
 
-use std::io::{self, Read, Write};
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use core::fmt;
+use core::str::{self, Utf8Error};
 
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::control::variable_header::ZeroPacketIdentifier;
 use crate::control::{FixedHeader, PacketType};
-use crate::packet::{DecodablePacket, PacketError};
+use crate::io::{self, Read, Write};
+use crate::packet::{DecodablePacket, PacketError, PubackPacket, PubrecPacket, VariablePacket};
 use crate::qos::QualityOfService;
 use crate::topic_name::TopicName;
 use crate::{control::variable_header::PacketIdentifier, TopicNameRef};
@@ -12,7 +19,8 @@ use crate::{Decodable, Encodable};
 use super::EncodablePacket;
 
 /// QoS with identifier pairs
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Ord, PartialOrd, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum QoSWithPacketIdentifier {
     Level0,
     Level1(u16),
@@ -20,6 +28,10 @@ pub enum QoSWithPacketIdentifier {
 }
 
 impl QoSWithPacketIdentifier {
+    /// Pairs `qos` with the packet identifier `id`
+    ///
+    /// `id` is ignored for QoS 0, as those messages have no packet identifier. It is not
+    /// checked otherwise, use `try_new` to reject the invalid identifier 0.
     pub fn new(qos: QualityOfService, id: u16) -> QoSWithPacketIdentifier {
         match (qos, id) {
             (QualityOfService::Level0, _) => QoSWithPacketIdentifier::Level0,
@@ -28,6 +40,15 @@ impl QoSWithPacketIdentifier {
         }
     }
 
+    /// Pairs `qos` with the packet identifier `id`, which must be non-zero for QoS 1 and 2 [MQTT-2.3.1-1]
+    pub fn try_new(qos: QualityOfService, id: u16) -> Result<QoSWithPacketIdentifier, ZeroPacketIdentifier> {
+        if qos != QualityOfService::Level0 && id == 0 {
+            return Err(ZeroPacketIdentifier);
+        }
+        Ok(QoSWithPacketIdentifier::new(qos, id))
+    }
+
+    /// Splits into the QoS level and the packet identifier, if any
     pub fn split(self) -> (QualityOfService, Option<u16>) {
         match self {
             QoSWithPacketIdentifier::Level0 => (QualityOfService::Level0, None),
@@ -35,10 +56,20 @@ impl QoSWithPacketIdentifier {
             QoSWithPacketIdentifier::Level2(pkid) => (QualityOfService::Level2, Some(pkid)),
         }
     }
+
+    /// Packet identifier, `None` for QoS 0
+    pub fn packet_identifier(self) -> Option<u16> {
+        self.split().1
+    }
 }
 
 /// `PUBLISH` packet
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(remote = "Self")
+)]
 pub struct PublishPacket {
     fixed_header: FixedHeader,
     topic_name: TopicName,
@@ -46,7 +77,11 @@ pub struct PublishPacket {
     payload: Vec<u8>,
 }
 
-encodable_packet!(PublishPacket(topic_name, packet_identifier, payload));
+encodable_packet!(
+    PublishPacket(topic_name, packet_identifier, payload),
+    validate = validate
+);
+serde_packet!(PublishPacket, Publish);
 
 impl PublishPacket {
     pub fn new<P: Into<Vec<u8>>>(topic_name: TopicName, qos: QoSWithPacketIdentifier, payload: P) -> PublishPacket {
@@ -61,6 +96,47 @@ impl PublishPacket {
         pk
     }
 
+    /// Like `new`, but copies a borrowed topic name, e.g. one returned by a topic matcher
+    pub fn new_from_ref<P: Into<Vec<u8>>>(
+        topic_name: &TopicNameRef,
+        qos: QoSWithPacketIdentifier,
+        payload: P,
+    ) -> PublishPacket {
+        PublishPacket::new(topic_name.to_owned(), qos, payload)
+    }
+
+    /// Creates a `PUBLISH` packet from a QoS level and the packet identifier it requires
+    ///
+    /// Returns an error if `qos` is 1 or 2 but `pkid` is `None` or zero [MQTT-2.3.1-1]. `pkid` is
+    /// ignored for QoS 0.
+    pub fn with_qos<P: Into<Vec<u8>>>(
+        topic_name: TopicName,
+        qos: QualityOfService,
+        pkid: Option<u16>,
+        payload: P,
+    ) -> Result<PublishPacket, PublishPacketError> {
+        let qos = match pkid {
+            Some(pkid) => QoSWithPacketIdentifier::try_new(qos, pkid)?,
+            None if qos == QualityOfService::Level0 => QoSWithPacketIdentifier::Level0,
+            None => return Err(PublishPacketError::MissingPacketIdentifier),
+        };
+        Ok(PublishPacket::new(topic_name, qos, payload))
+    }
+
+    /// Creates a retained QoS 0 `PUBLISH` with an empty payload, which removes the message
+    /// retained for `topic_name` on the server [MQTT-3.3.1-10]
+    pub fn clear_retained(topic_name: TopicName) -> PublishPacket {
+        let mut pk = PublishPacket::new(topic_name, QoSWithPacketIdentifier::Level0, Vec::new());
+        pk.set_retain(true);
+        pk
+    }
+
+    /// Whether this packet removes the retained message of its topic, i.e. it is retained and
+    /// has an empty payload
+    pub fn is_retained_clear(&self) -> bool {
+        self.retain() && self.payload.is_empty()
+    }
+
     pub fn set_dup(&mut self, dup: bool) {
         self.fixed_header
             .packet_type
@@ -68,7 +144,7 @@ impl PublishPacket {
     }
 
     pub fn dup(&self) -> bool {
-        self.fixed_header.packet_type.flags() & 0x80 != 0
+        self.fixed_header.packet_type.flags() & 0b1000 != 0
     }
 
     pub fn set_qos(&mut self, qos: QoSWithPacketIdentifier) {
@@ -80,18 +156,66 @@ impl PublishPacket {
         self.fix_header_remaining_len();
     }
 
-    pub fn qos(&self) -> QoSWithPacketIdentifier {
-        match self.packet_identifier {
-            None => QoSWithPacketIdentifier::Level0,
-            Some(pkid) => {
-                let qos_val = (self.fixed_header.packet_type.flags() & 0b0110) >> 1;
-                match qos_val {
-                    1 => QoSWithPacketIdentifier::Level1(pkid.0),
-                    2 => QoSWithPacketIdentifier::Level2(pkid.0),
-                    _ => unreachable!(),
-                }
+    /// Like `set_qos`, but rejects a zero packet identifier and switching to QoS 0 while the DUP
+    /// flag is set, which would make the packet invalid
+    pub fn try_set_qos(&mut self, qos: QoSWithPacketIdentifier) -> Result<(), PublishPacketError> {
+        let (level, pkid) = qos.split();
+        let flags = (self.fixed_header.packet_type.flags() & !0b0110) | (level as u8) << 1;
+        PublishPacket::validate_flags(flags, pkid.map(PacketIdentifier))?;
+        self.set_qos(qos);
+        Ok(())
+    }
+
+    /// Checks the fixed header flags against the packet identifier
+    ///
+    /// Setters such as `set_dup` do not check the other fields, so a mutated packet may break these
+    /// rules. Encoding the packet fails in that case.
+    pub fn validate(&self) -> Result<(), PublishPacketError> {
+        PublishPacket::validate_flags(self.fixed_header.packet_type.flags(), self.packet_identifier)
+    }
+
+    /// Checks the flags of a `PUBLISH` fixed header against its packet identifier, for encoding,
+    /// decoding and the checked setters alike
+    fn validate_flags(flags: u8, packet_identifier: Option<PacketIdentifier>) -> Result<(), PublishPacketError> {
+        let qos = match (flags & 0b0110) >> 1 {
+            0 => QualityOfService::Level0,
+            1 => QualityOfService::Level1,
+            2 => QualityOfService::Level2,
+            // `PacketType` never has both QoS bits set [MQTT-3.3.1-4]
+            _ => unreachable!(),
+        };
+        match packet_identifier {
+            // QoS 1 and 2 messages always carry a non-zero packet identifier [MQTT-2.3.1-1]
+            Some(pkid) if qos != QualityOfService::Level0 => {
+                QoSWithPacketIdentifier::try_new(qos, pkid.0)?;
             }
+            None if qos != QualityOfService::Level0 => return Err(PublishPacketError::MissingPacketIdentifier),
+            // QoS 0 messages must not carry one [MQTT-2.3.1-5]
+            Some(_) => return Err(PublishPacketError::UnexpectedPacketIdentifier),
+            None => {}
+        }
+        if qos == QualityOfService::Level0 && flags & 0b1000 != 0 {
+            // The DUP flag must be 0 for all QoS 0 messages [MQTT-3.3.1-2]
+            return Err(PublishPacketError::InvalidDupForQoS0);
         }
+        Ok(())
+    }
+
+    /// QoS level from the fixed header, paired with the packet identifier
+    pub fn qos(&self) -> QoSWithPacketIdentifier {
+        // Both `set_qos` and decoding ensure an identifier is present for QoS 1 and 2
+        let pkid = self.packet_identifier.map_or(0, |pkid| pkid.0);
+        match self.raw_qos() {
+            0 => QoSWithPacketIdentifier::Level0,
+            1 => QoSWithPacketIdentifier::Level1(pkid),
+            2 => QoSWithPacketIdentifier::Level2(pkid),
+            _ => unreachable!(),
+        }
+    }
+
+    /// The two QoS bits from the fixed header, regardless of the packet identifier
+    pub fn raw_qos(&self) -> u8 {
+        (self.fixed_header.packet_type.flags() & 0b0110) >> 1
     }
 
     pub fn set_retain(&mut self, ret: bool) {
@@ -117,20 +241,130 @@ impl PublishPacket {
         &self.payload
     }
 
+    /// The payload as UTF-8 text
+    pub fn payload_str(&self) -> Result<&str, Utf8Error> {
+        str::from_utf8(&self.payload)
+    }
+
+    /// The packet the receiver answers with: nothing for QoS 0, `PUBACK` for QoS 1 and `PUBREC`
+    /// for QoS 2, with the packet identifier of this packet
+    pub fn acknowledgement(&self) -> Option<VariablePacket> {
+        match self.qos() {
+            QoSWithPacketIdentifier::Level0 => None,
+            QoSWithPacketIdentifier::Level1(pkid) => Some(PubackPacket::new(pkid).into()),
+            QoSWithPacketIdentifier::Level2(pkid) => Some(PubrecPacket::new(pkid).into()),
+        }
+    }
+
+    /// Borrows this packet under another topic name, e.g. for a bridge rewriting topic prefixes
+    ///
+    /// The QoS, packet identifier, DUP and retain flags are kept, and the payload is not copied.
+    pub fn with_topic<'a>(&'a self, topic_name: &'a TopicNameRef) -> PublishPacketRef<'a> {
+        let mut pk = PublishPacketRef {
+            fixed_header: self.fixed_header,
+            topic_name,
+            packet_identifier: self.packet_identifier,
+            payload: &self.payload,
+        };
+        pk.fix_header_remaining_len();
+        pk
+    }
+
+    /// Borrows the fields of this packet, the resulting packet encodes to the same bytes
+    pub fn as_ref(&self) -> PublishPacketRef<'_> {
+        PublishPacketRef {
+            fixed_header: self.fixed_header,
+            topic_name: &self.topic_name,
+            packet_identifier: self.packet_identifier,
+            payload: &self.payload,
+        }
+    }
+
+    /// Length in bytes of the encoded packet, equivalent to `encoded_length`
+    ///
+    /// The remaining length in the fixed header is kept up to date by every setter, so this does not
+    /// walk the topic name and payload.
+    pub fn wire_size(&self) -> u32 {
+        self.fixed_header
+            .encoded_length()
+            .saturating_add(self.fixed_header.remaining_length)
+    }
+
+    /// Length of the payload in bytes
+    pub fn payload_len(&self) -> usize {
+        self.payload.len()
+    }
+
+    /// At most `max` bytes of the payload, for logging large messages safely
+    pub fn payload_preview(&self, max: usize) -> &[u8] {
+        &self.payload[..self.payload.len().min(max)]
+    }
+
     pub fn set_payload<P: Into<Vec<u8>>>(&mut self, payload: P) {
         self.payload = payload.into();
         self.fix_header_remaining_len();
     }
+
+    /// Encodes the packet for retransmission, with the DUP flag set in the fixed header
+    ///
+    /// The packet itself is not modified. QoS 0 messages are never retransmitted, so the DUP flag is
+    /// left clear for them [MQTT-3.3.1-2].
+    pub fn encode_retransmit<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.try_encoded_length()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        let mut fixed_header = self.fixed_header;
+        if self.packet_identifier.is_some() {
+            fixed_header.packet_type.update_flags(|flags| flags | (1 << 3));
+        }
+        fixed_header.encode(writer)?;
+        self.encode_packet(writer)
+    }
+
+    /// Asynchronously write data after the fixed header, without copying the payload
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn write_packet_to<A: AsyncWrite + Unpin>(&self, wr: &mut A) -> io::Result<()> {
+        self.validate()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let vhead_len = self.topic_name.encoded_length() + self.packet_identifier.encoded_length();
+        let mut buf = Vec::with_capacity(vhead_len as usize);
+        self.topic_name.encode(&mut buf)?;
+        self.packet_identifier.encode(&mut buf)?;
+        wr.write_all(&buf).await?;
+        wr.write_all(&self.payload).await
+    }
+}
+
+impl fmt::Display for PublishPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (qos, pkid) = self.qos().split();
+        write!(f, "PUBLISH topic={} qos={}", self.topic_name(), qos as u8)?;
+        if let Some(pkid) = pkid {
+            write!(f, " id={}", pkid)?;
+        }
+        write!(
+            f,
+            " retain={} dup={} len={} payload={:?}",
+            self.retain(),
+            self.dup(),
+            self.payload.len(),
+            String::from_utf8_lossy(self.payload_preview(32))
+        )
+    }
 }
 
 impl DecodablePacket for PublishPacket {
-    type DecodePacketError = std::convert::Infallible;
+    type DecodePacketError = PublishPacketError;
 
     fn decode_packet<R: Read>(reader: &mut R, fixed_header: FixedHeader) -> Result<Self, PacketError<Self>> {
+        let qos = (fixed_header.packet_type.flags() & 0b0110) >> 1;
         let topic_name = TopicName::decode(reader)?;
 
-        let qos = (fixed_header.packet_type.flags() & 0b0110) >> 1;
         let packet_identifier = if qos > 0 {
+            // QoS 1 and 2 messages always carry a packet identifier [MQTT-2.3.1-1]
+            if fixed_header.remaining_length < topic_name.encoded_length() + 2 {
+                return Err(PacketError::PayloadError(PublishPacketError::MissingPacketIdentifier));
+            }
             Some(PacketIdentifier::decode(reader)?)
         } else {
             None
@@ -138,19 +372,43 @@ impl DecodablePacket for PublishPacket {
 
         let vhead_len =
             topic_name.encoded_length() + packet_identifier.as_ref().map(|x| x.encoded_length()).unwrap_or(0);
-        let payload_len = fixed_header.remaining_length - vhead_len;
+        let payload_len = fixed_header
+            .remaining_length
+            .checked_sub(vhead_len)
+            .ok_or(PacketError::PayloadError(PublishPacketError::RemainingLengthTooSmall {
+                remaining_length: fixed_header.remaining_length,
+                header_length: vhead_len,
+            }))?;
 
         let payload = Vec::<u8>::decode_with(reader, Some(payload_len))?;
 
-        Ok(PublishPacket {
+        let packet = PublishPacket {
             fixed_header,
             topic_name,
             packet_identifier,
             payload,
-        })
+        };
+        packet.validate().map_err(PacketError::PayloadError)?;
+        Ok(packet)
     }
 }
 
+/// A `PUBLISH` packet breaking the rules on its QoS, packet identifier and DUP flag, when decoded or
+/// validated
+#[derive(Debug, Eq, PartialEq, Copy, Clone, thiserror::Error)]
+pub enum PublishPacketError {
+    #[error("DUP flag is set for QoS 0 message")]
+    InvalidDupForQoS0,
+    #[error("remaining length {remaining_length} is smaller than variable header length {header_length}")]
+    RemainingLengthTooSmall { remaining_length: u32, header_length: u32 },
+    #[error("missing packet identifier for QoS 1 or 2 message")]
+    MissingPacketIdentifier,
+    #[error("packet identifier set for QoS 0 message")]
+    UnexpectedPacketIdentifier,
+    #[error(transparent)]
+    ZeroPacketIdentifier(#[from] ZeroPacketIdentifier),
+}
+
 /// `PUBLISH` packet by reference, for encoding only
 pub struct PublishPacketRef<'a> {
     fixed_header: FixedHeader,
@@ -173,6 +431,16 @@ impl<'a> PublishPacketRef<'a> {
         pk
     }
 
+    /// Copies the topic name and payload into an owned `PublishPacket`, keeping all the flags
+    pub fn to_owned(&self) -> PublishPacket {
+        PublishPacket {
+            fixed_header: self.fixed_header,
+            topic_name: self.topic_name.to_owned(),
+            packet_identifier: self.packet_identifier,
+            payload: self.payload.to_vec(),
+        }
+    }
+
     fn fix_header_remaining_len(&mut self) {
         self.fixed_header.remaining_length =
             self.topic_name.encoded_length() + self.packet_identifier.encoded_length() + self.payload.encoded_length();
@@ -195,12 +463,14 @@ impl EncodablePacket for PublishPacketRef<'_> {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
 
     use std::io::Cursor;
 
+    use crate::control::fixed_header::FixedHeaderError;
+    use crate::control::packet_type::{ControlType, InvalidFlag, PacketTypeError};
     use crate::topic_name::TopicName;
     use crate::{Decodable, Encodable};
 
@@ -221,6 +491,337 @@ mod test {
         assert_eq!(packet, decoded);
     }
 
+    #[test]
+    fn test_publish_packet_new_from_ref() {
+        let topic_name = TopicNameRef::new("a/b").unwrap();
+        let packet = PublishPacket::new_from_ref(topic_name, QoSWithPacketIdentifier::Level1(10), "x");
+        assert_eq!(
+            packet,
+            PublishPacket::new(TopicName::new("a/b").unwrap(), QoSWithPacketIdentifier::Level1(10), "x")
+        );
+    }
+
+    #[test]
+    fn test_publish_packet_with_qos() {
+        let topic_name = TopicName::new("a/b").unwrap();
+        let packet = PublishPacket::with_qos(topic_name.clone(), QualityOfService::Level2, Some(10), "x").unwrap();
+        assert_eq!(
+            packet,
+            PublishPacket::new(topic_name.clone(), QoSWithPacketIdentifier::Level2(10), "x")
+        );
+
+        let packet = PublishPacket::with_qos(topic_name.clone(), QualityOfService::Level0, Some(10), "x").unwrap();
+        assert_eq!(packet.qos(), QoSWithPacketIdentifier::Level0);
+
+        assert_eq!(
+            PublishPacket::with_qos(topic_name.clone(), QualityOfService::Level1, None, "x"),
+            Err(PublishPacketError::MissingPacketIdentifier)
+        );
+        assert_eq!(
+            PublishPacket::with_qos(topic_name.clone(), QualityOfService::Level1, Some(0), "x"),
+            Err(PublishPacketError::ZeroPacketIdentifier(ZeroPacketIdentifier))
+        );
+        assert_eq!(
+            PublishPacket::with_qos(topic_name, QualityOfService::Level2, Some(0), "x"),
+            Err(PublishPacketError::ZeroPacketIdentifier(ZeroPacketIdentifier))
+        );
+    }
+
+    #[test]
+    fn test_publish_packet_clear_retained() {
+        let packet = PublishPacket::clear_retained(TopicName::new("a/b").unwrap());
+        assert!(packet.retain());
+        assert!(packet.payload().is_empty());
+        assert_eq!(packet.qos(), QoSWithPacketIdentifier::Level0);
+        assert!(packet.is_retained_clear());
+
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+        assert_eq!(buf, b"\x31\x05\x00\x03a/b");
+
+        let mut packet = PublishPacket::new(TopicName::new("a/b").unwrap(), QoSWithPacketIdentifier::Level0, "x");
+        packet.set_retain(true);
+        assert!(!packet.is_retained_clear());
+    }
+
+    #[test]
+    fn test_qos_with_packet_identifier_split() {
+        let qos = QoSWithPacketIdentifier::new(QualityOfService::Level1, 10);
+        assert_eq!(qos.split(), (QualityOfService::Level1, Some(10)));
+        assert_eq!(qos.packet_identifier(), Some(10));
+
+        let qos = QoSWithPacketIdentifier::new(QualityOfService::Level0, 10);
+        assert_eq!(qos.split(), (QualityOfService::Level0, None));
+        assert_eq!(qos.packet_identifier(), None);
+    }
+
+    #[test]
+    fn test_qos_with_packet_identifier_try_new() {
+        let qos = QoSWithPacketIdentifier::try_new(QualityOfService::Level2, 1).unwrap();
+        assert_eq!(qos, QoSWithPacketIdentifier::Level2(1));
+
+        let qos = QoSWithPacketIdentifier::try_new(QualityOfService::Level0, 0).unwrap();
+        assert_eq!(qos, QoSWithPacketIdentifier::Level0);
+
+        assert!(QoSWithPacketIdentifier::try_new(QualityOfService::Level1, 0).is_err());
+    }
+
+    #[test]
+    fn test_publish_packet_acknowledgement() {
+        let topic_name = TopicName::new("a/b").unwrap();
+        let packet = PublishPacket::new(topic_name.clone(), QoSWithPacketIdentifier::Level0, "x");
+        assert_eq!(packet.acknowledgement(), None);
+
+        let packet = PublishPacket::new(topic_name.clone(), QoSWithPacketIdentifier::Level1(10), "x");
+        assert_eq!(packet.acknowledgement(), Some(PubackPacket::new(10).into()));
+
+        let packet = PublishPacket::new(topic_name, QoSWithPacketIdentifier::Level2(10), "x");
+        let pubrec = packet.acknowledgement().unwrap().into_pubrec().unwrap();
+        assert_eq!(pubrec.packet_identifier(), 10);
+        let pubrel = pubrec.release();
+        assert_eq!(pubrel.packet_identifier(), 10);
+        assert_eq!(pubrel.complete().packet_identifier(), 10);
+    }
+
+    #[test]
+    fn test_publish_packet_with_topic() {
+        let mut packet = PublishPacket::new(TopicName::new("a/b").unwrap(), QoSWithPacketIdentifier::Level2(10), "x");
+        packet.set_retain(true);
+        packet.set_dup(true);
+
+        let topic_name = TopicName::new("bridge/a/b").unwrap();
+        let mut buf = Vec::new();
+        packet.with_topic(&topic_name).encode(&mut buf).unwrap();
+
+        let mut expected = packet.clone();
+        expected.set_topic_name(topic_name);
+        assert_eq!(PublishPacket::decode(&mut Cursor::new(buf)).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_publish_packet_as_ref() {
+        let mut packet = PublishPacket::new(TopicName::new("a/b").unwrap(), QoSWithPacketIdentifier::Level1(10), "x");
+        packet.set_retain(true);
+
+        let packet_ref = packet.as_ref();
+        let mut buf = Vec::new();
+        packet_ref.encode(&mut buf).unwrap();
+        let mut expected = Vec::new();
+        packet.encode(&mut expected).unwrap();
+        assert_eq!(buf, expected);
+
+        assert_eq!(packet_ref.to_owned(), packet);
+        let topic_name = TopicName::new("c").unwrap();
+        let packet_ref = PublishPacketRef::new(&topic_name, QoSWithPacketIdentifier::Level0, b"y");
+        assert_eq!(
+            packet_ref.to_owned(),
+            PublishPacket::new(topic_name.clone(), QoSWithPacketIdentifier::Level0, "y")
+        );
+    }
+
+    #[test]
+    fn test_publish_packet_validate() {
+        let mut packet = PublishPacket::new(TopicName::new("a/b").unwrap(), QoSWithPacketIdentifier::Level1(10), "x");
+        assert_eq!(packet.validate(), Ok(()));
+        packet.set_dup(true);
+        assert_eq!(
+            packet.try_set_qos(QoSWithPacketIdentifier::Level0),
+            Err(PublishPacketError::InvalidDupForQoS0)
+        );
+        assert_eq!(
+            packet.try_set_qos(QoSWithPacketIdentifier::Level2(0)),
+            Err(PublishPacketError::ZeroPacketIdentifier(ZeroPacketIdentifier))
+        );
+        assert_eq!(packet.qos(), QoSWithPacketIdentifier::Level1(10));
+
+        // The unchecked setter leaves a QoS 0 message with the DUP flag
+        packet.set_qos(QoSWithPacketIdentifier::Level0);
+        assert_eq!(packet.validate(), Err(PublishPacketError::InvalidDupForQoS0));
+        let err = packet.encode(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let mut buf = vec![0xFF];
+        let err = packet.encode_into(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(buf, [0xFF]);
+
+        packet.set_dup(false);
+        assert_eq!(packet.validate(), Ok(()));
+        packet.encode(&mut Vec::new()).unwrap();
+    }
+
+    #[test]
+    fn test_publish_packet_payload_str() {
+        let mut packet = PublishPacket::new(TopicName::new("a/b").unwrap(), QoSWithPacketIdentifier::Level0, "héllo");
+        assert_eq!(packet.payload_str(), Ok("héllo"));
+        packet.set_payload(&b"\xFFx"[..]);
+        assert_eq!(packet.payload_str().unwrap_err().valid_up_to(), 0);
+    }
+
+    #[test]
+    fn test_publish_packet_dup() {
+        let mut packet = PublishPacket::new(TopicName::new("a/b").unwrap(), QoSWithPacketIdentifier::Level1(10), "x");
+        assert!(!packet.dup());
+        packet.set_dup(true);
+        assert!(packet.dup());
+        assert_eq!(packet.fixed_header().packet_type.flags(), 0b1010);
+        assert_eq!(packet.qos(), QoSWithPacketIdentifier::Level1(10));
+
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+        assert_eq!(buf[0], 0x3A);
+        assert!(PublishPacket::decode(&mut Cursor::new(buf)).unwrap().dup());
+
+        packet.set_dup(false);
+        assert!(!packet.dup());
+        assert_eq!(packet.fixed_header().packet_type.flags(), 0b0010);
+    }
+
+    #[test]
+    fn test_publish_packet_set_flags() {
+        let mut packet = PublishPacket::new(TopicName::new("a/b").unwrap(), QoSWithPacketIdentifier::Level2(10), "x");
+        let flags = |packet: &PublishPacket| packet.fixed_header().packet_type.flags();
+        assert_eq!(flags(&packet), 0b0100);
+
+        for _ in 0..2 {
+            packet.set_qos(QoSWithPacketIdentifier::Level1(10));
+            assert_eq!(flags(&packet), 0b0010);
+            packet.set_retain(true);
+            assert_eq!(flags(&packet), 0b0011);
+            packet.set_qos(QoSWithPacketIdentifier::Level2(10));
+            assert_eq!(flags(&packet), 0b0101);
+            packet.set_retain(false);
+            assert_eq!(flags(&packet), 0b0100);
+            packet.set_qos(QoSWithPacketIdentifier::Level0);
+            assert_eq!(flags(&packet), 0b0000);
+            packet.set_qos(QoSWithPacketIdentifier::Level2(10));
+            assert_eq!(flags(&packet), 0b0100);
+        }
+        assert_eq!(packet.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_publish_packet_payload_preview() {
+        let packet = PublishPacket::new(
+            TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level0,
+            vec![0u8; 1000],
+        );
+        assert_eq!(packet.payload_preview(16).len(), 16);
+        assert_eq!(packet.payload_preview(2000).len(), 1000);
+        assert_eq!(packet.payload_len(), 1000);
+        assert_eq!(packet.wire_size(), packet.encoded_length());
+        assert_eq!(packet.wire_size(), packet.total_encoded_length());
+        assert_eq!(packet.wire_size(), 3 + 5 + 1000);
+    }
+
+    #[test]
+    fn test_publish_packet_raw_qos() {
+        let packet = PublishPacket::new(
+            TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level2(10),
+            Vec::new(),
+        );
+        assert_eq!(packet.raw_qos(), 2);
+
+        let packet = PublishPacket::new(
+            TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level0,
+            Vec::new(),
+        );
+        assert_eq!(packet.raw_qos(), 0);
+
+        // QoS bits 3 are rejected as soon as the fixed header is parsed [MQTT-3.3.1-4]
+        let mut decode_buf = Cursor::new(&b"\x36\x05\x00\x03a/b"[..]);
+        match PublishPacket::decode(&mut decode_buf) {
+            Err(PacketError::FixedHeaderError(FixedHeaderError::PacketTypeError(PacketTypeError::InvalidFlag(
+                InvalidFlag(ControlType::Publish, 0b0110),
+            )))) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_publish_packet_dup_qos0() {
+        let mut decode_buf = Cursor::new(&b"\x38\x05\x00\x03a/b"[..]);
+        match PublishPacket::decode(&mut decode_buf) {
+            Err(PacketError::PayloadError(PublishPacketError::InvalidDupForQoS0)) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        let mut decode_buf = Cursor::new(&b"\x3A\x07\x00\x03a/b\x00\x01"[..]);
+        let decoded = PublishPacket::decode(&mut decode_buf).unwrap();
+        assert_eq!(decoded.qos(), QoSWithPacketIdentifier::Level1(1));
+        assert!(decoded.dup());
+    }
+
+    #[test]
+    fn test_publish_packet_error_source() {
+        use std::error::Error;
+
+        use crate::packet::VariablePacket;
+
+        let err = PublishPacket::decode(&mut Cursor::new(&b"\x38\x05\x00\x03a/b"[..])).unwrap_err();
+        let source = err.source().expect("payload error as source");
+        assert!(matches!(
+            source.downcast_ref::<PublishPacketError>(),
+            Some(PublishPacketError::InvalidDupForQoS0)
+        ));
+        assert_eq!(err.to_string(), source.to_string());
+
+        let err = VariablePacket::decode(&mut Cursor::new(&b"\x38\x05\x00\x03a/b"[..])).unwrap_err();
+        assert!(err.source().unwrap().is::<PublishPacketError>());
+    }
+
+    #[test]
+    fn test_publish_packet_missing_packet_identifier() {
+        // QoS 1 bits, but the packet ends right after the topic name
+        let mut decode_buf = Cursor::new(&b"\x32\x05\x00\x03a/b"[..]);
+        match PublishPacket::decode(&mut decode_buf) {
+            Err(PacketError::PayloadError(PublishPacketError::MissingPacketIdentifier)) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        let mut decode_buf = Cursor::new(&b"\x32\x07\x00\x03a/b\x00\x0A"[..]);
+        let decoded = PublishPacket::decode(&mut decode_buf).unwrap();
+        assert_eq!(decoded.qos(), QoSWithPacketIdentifier::Level1(10));
+    }
+
+    #[test]
+    fn test_publish_packet_remaining_length_too_small() {
+        // Remaining length 2 cannot hold the 5 bytes topic name
+        let fixed_header = FixedHeader::new(PacketType::publish(QualityOfService::Level0), 2);
+        let mut decode_buf = Cursor::new(&b"\x00\x03a/b"[..]);
+        match PublishPacket::decode_packet(&mut decode_buf, fixed_header) {
+            Err(PacketError::PayloadError(PublishPacketError::RemainingLengthTooSmall {
+                remaining_length: 2,
+                header_length: 5,
+            })) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_publish_packet_encode_retransmit() {
+        let packet = PublishPacket::new(
+            TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level1(10),
+            b"Hello world!".to_vec(),
+        );
+
+        let mut buf = Vec::new();
+        packet.encode_retransmit(&mut buf).unwrap();
+        assert_eq!(buf[0], 0x3A);
+        assert!(!packet.dup());
+
+        let mut expected = Vec::new();
+        packet.encode(&mut expected).unwrap();
+        assert_eq!(&buf[1..], &expected[1..]);
+
+        let decoded = PublishPacket::decode(&mut Cursor::new(buf)).unwrap();
+        assert_eq!(decoded.payload(), packet.payload());
+        assert!(decoded.dup());
+    }
+
     #[test]
     fn issue56() {
         let mut packet = PublishPacket::new(
@@ -1,20 +1,27 @@
 //! PUBREC
 
-use std::io::Read;
+use core::fmt;
 
 use crate::control::variable_header::PacketIdentifier;
 use crate::control::{ControlType, FixedHeader, PacketType};
-use crate::packet::{DecodablePacket, PacketError};
+use crate::io::Read;
+use crate::packet::{DecodablePacket, PacketError, PubrelPacket};
 use crate::Decodable;
 
 /// `PUBREC` packet
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(remote = "Self")
+)]
 pub struct PubrecPacket {
     fixed_header: FixedHeader,
     packet_identifier: PacketIdentifier,
 }
 
 encodable_packet!(PubrecPacket(packet_identifier));
+serde_packet!(PubrecPacket, PublishReceived);
 
 impl PubrecPacket {
     pub fn new(pkid: u16) -> PubrecPacket {
@@ -31,10 +38,21 @@ impl PubrecPacket {
     pub fn set_packet_identifier(&mut self, pkid: u16) {
         self.packet_identifier.0 = pkid;
     }
+
+    /// The `PUBREL` the sender of the QoS 2 message answers with [MQTT-4.3.3-1]
+    pub fn release(&self) -> PubrelPacket {
+        PubrelPacket::new(self.packet_identifier.0)
+    }
+}
+
+impl fmt::Display for PubrecPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PUBREC id={}", self.packet_identifier.0)
+    }
 }
 
 impl DecodablePacket for PubrecPacket {
-    type DecodePacketError = std::convert::Infallible;
+    type DecodePacketError = core::convert::Infallible;
 
     fn decode_packet<R: Read>(reader: &mut R, fixed_header: FixedHeader) -> Result<Self, PacketError<Self>> {
         let packet_identifier: PacketIdentifier = PacketIdentifier::decode(reader)?;
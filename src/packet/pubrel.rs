@@ -1,20 +1,27 @@
 //! PUBREL
 
-use std::io::Read;
+use core::fmt;
 
 use crate::control::variable_header::PacketIdentifier;
 use crate::control::{ControlType, FixedHeader, PacketType};
-use crate::packet::{DecodablePacket, PacketError};
+use crate::io::Read;
+use crate::packet::{DecodablePacket, PacketError, PubcompPacket};
 use crate::Decodable;
 
 /// `PUBREL` packet
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(remote = "Self")
+)]
 pub struct PubrelPacket {
     fixed_header: FixedHeader,
     packet_identifier: PacketIdentifier,
 }
 
 encodable_packet!(PubrelPacket(packet_identifier));
+serde_packet!(PubrelPacket, PublishRelease);
 
 impl PubrelPacket {
     pub fn new(pkid: u16) -> PubrelPacket {
@@ -31,10 +38,21 @@ impl PubrelPacket {
     pub fn set_packet_identifier(&mut self, pkid: u16) {
         self.packet_identifier.0 = pkid;
     }
+
+    /// The `PUBCOMP` the receiver of the QoS 2 message answers with [MQTT-4.3.3-2]
+    pub fn complete(&self) -> PubcompPacket {
+        PubcompPacket::new(self.packet_identifier.0)
+    }
+}
+
+impl fmt::Display for PubrelPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PUBREL id={}", self.packet_identifier.0)
+    }
 }
 
 impl DecodablePacket for PubrelPacket {
-    type DecodePacketError = std::convert::Infallible;
+    type DecodePacketError = core::convert::Infallible;
 
     fn decode_packet<R: Read>(reader: &mut R, fixed_header: FixedHeader) -> Result<Self, PacketError<Self>> {
         let packet_identifier: PacketIdentifier = PacketIdentifier::decode(reader)?;
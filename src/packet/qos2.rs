@@ -0,0 +1,216 @@
+//! QoS 2 delivery handshake
+
+use crate::control::ControlType;
+use crate::packet::{PubcompPacket, PublishPacket, PubrecPacket, PubrelPacket, QoSWithPacketIdentifier};
+
+/// State of one QoS 2 message, on the sender or the receiver side
+///
+/// The sender goes through `PUBLISH` → `PUBREC` → `PUBREL` → `PUBCOMP`, the receiver answers
+/// `PUBLISH` with `PUBREC` and `PUBREL` with `PUBCOMP`. Each transition checks the packet
+/// identifier and the order of the packets, and returns the packet to send next.
+///
+/// ```rust
+/// use mqtt::packet::{PubrecPacket, QoS2State};
+///
+/// // Sender, the PUBLISH with identifier 10 has been sent
+/// let mut state = QoS2State::publish_sent(10);
+/// let pubrel = state.on_pubrec(&PubrecPacket::new(10)).unwrap();
+/// assert_eq!(state, QoS2State::AwaitingPubcomp(10));
+///
+/// // Receiver
+/// let (mut peer, pubrec) = QoS2State::publish_received(10);
+/// let pubcomp = peer.on_pubrel(&pubrel).unwrap();
+///
+/// state.on_pubcomp(&pubcomp).unwrap();
+/// assert!(state.is_complete() && peer.is_complete());
+/// ```
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum QoS2State {
+    /// Sender, the `PUBLISH` has been sent
+    AwaitingPubrec(u16),
+    /// Sender, the `PUBREL` has been sent
+    AwaitingPubcomp(u16),
+    /// Receiver, the `PUBREC` has been sent
+    AwaitingPubrel(u16),
+    /// The handshake is over, the packet identifier can be reused
+    Complete(u16),
+}
+
+impl QoS2State {
+    /// State of the sender of a QoS 2 `PUBLISH` with identifier `pkid`
+    pub fn publish_sent(pkid: u16) -> QoS2State {
+        QoS2State::AwaitingPubrec(pkid)
+    }
+
+    /// State of the receiver of a QoS 2 `PUBLISH` with identifier `pkid`, with the `PUBREC` to
+    /// answer it
+    pub fn publish_received(pkid: u16) -> (QoS2State, PubrecPacket) {
+        (QoS2State::AwaitingPubrel(pkid), PubrecPacket::new(pkid))
+    }
+
+    /// State of the receiver of `packet`, or `None` if it is not a QoS 2 message
+    pub fn from_publish(packet: &PublishPacket) -> Option<(QoS2State, PubrecPacket)> {
+        match packet.qos() {
+            QoSWithPacketIdentifier::Level2(pkid) => Some(QoS2State::publish_received(pkid)),
+            _ => None,
+        }
+    }
+
+    pub fn packet_identifier(self) -> u16 {
+        match self {
+            QoS2State::AwaitingPubrec(pkid)
+            | QoS2State::AwaitingPubcomp(pkid)
+            | QoS2State::AwaitingPubrel(pkid)
+            | QoS2State::Complete(pkid) => pkid,
+        }
+    }
+
+    pub fn is_complete(self) -> bool {
+        matches!(self, QoS2State::Complete(..))
+    }
+
+    /// Sender receives `PUBREC`, returns the `PUBREL` to send
+    ///
+    /// A duplicate `PUBREC` while waiting for `PUBCOMP` means the `PUBREL` may have been lost, so it
+    /// is returned again.
+    pub fn on_pubrec(&mut self, packet: &PubrecPacket) -> Result<PubrelPacket, QoS2Error> {
+        self.check(ControlType::PublishReceived, packet.packet_identifier())?;
+        match *self {
+            QoS2State::AwaitingPubrec(pkid) => {
+                *self = QoS2State::AwaitingPubcomp(pkid);
+                Ok(packet.release())
+            }
+            QoS2State::AwaitingPubcomp(..) => Ok(packet.release()),
+            state => Err(QoS2Error::UnexpectedPacket {
+                state,
+                received: ControlType::PublishReceived,
+            }),
+        }
+    }
+
+    /// Receiver receives `PUBREL`, returns the `PUBCOMP` to send
+    ///
+    /// The receiver must answer every `PUBREL` with a `PUBCOMP`, including a duplicate one sent
+    /// because the first `PUBCOMP` was lost [MQTT-4.3.3-2].
+    pub fn on_pubrel(&mut self, packet: &PubrelPacket) -> Result<PubcompPacket, QoS2Error> {
+        self.check(ControlType::PublishRelease, packet.packet_identifier())?;
+        match *self {
+            QoS2State::AwaitingPubrel(pkid) => {
+                *self = QoS2State::Complete(pkid);
+                Ok(packet.complete())
+            }
+            QoS2State::Complete(..) => Ok(packet.complete()),
+            state => Err(QoS2Error::UnexpectedPacket {
+                state,
+                received: ControlType::PublishRelease,
+            }),
+        }
+    }
+
+    /// Sender receives `PUBCOMP`, which ends the handshake
+    pub fn on_pubcomp(&mut self, packet: &PubcompPacket) -> Result<(), QoS2Error> {
+        self.check(ControlType::PublishComplete, packet.packet_identifier())?;
+        match *self {
+            QoS2State::AwaitingPubcomp(pkid) => {
+                *self = QoS2State::Complete(pkid);
+                Ok(())
+            }
+            state => Err(QoS2Error::UnexpectedPacket {
+                state,
+                received: ControlType::PublishComplete,
+            }),
+        }
+    }
+
+    fn check(self, received: ControlType, pkid: u16) -> Result<(), QoS2Error> {
+        let expected = self.packet_identifier();
+        if pkid != expected {
+            return Err(QoS2Error::PacketIdentifierMismatch {
+                received,
+                expected,
+                found: pkid,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Packet received out of order in a QoS 2 handshake, the state is left unchanged
+#[derive(Debug, Eq, PartialEq, Copy, Clone, thiserror::Error)]
+pub enum QoS2Error {
+    /// The packet is for another message
+    #[error("{received:?} for packet identifier {found}, expected {expected}")]
+    PacketIdentifierMismatch {
+        received: ControlType,
+        expected: u16,
+        found: u16,
+    },
+    /// The packet does not follow the current state, e.g. a `PUBCOMP` before the `PUBREC`
+    #[error("unexpected {received:?} in state {state:?}")]
+    UnexpectedPacket { state: QoS2State, received: ControlType },
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_qos2_sender() {
+        let mut state = QoS2State::publish_sent(10);
+        assert_eq!(
+            state.on_pubrec(&PubrecPacket::new(11)),
+            Err(QoS2Error::PacketIdentifierMismatch {
+                received: ControlType::PublishReceived,
+                expected: 10,
+                found: 11,
+            })
+        );
+        assert!(matches!(
+            state.on_pubcomp(&PubcompPacket::new(10)),
+            Err(QoS2Error::UnexpectedPacket {
+                state: QoS2State::AwaitingPubrec(10),
+                ..
+            })
+        ));
+
+        assert_eq!(state.on_pubrec(&PubrecPacket::new(10)), Ok(PubrelPacket::new(10)));
+        // Duplicate, the PUBREL is sent again
+        assert_eq!(state.on_pubrec(&PubrecPacket::new(10)), Ok(PubrelPacket::new(10)));
+        assert_eq!(state, QoS2State::AwaitingPubcomp(10));
+
+        assert_eq!(state.on_pubcomp(&PubcompPacket::new(10)), Ok(()));
+        assert_eq!(state, QoS2State::Complete(10));
+        assert!(matches!(
+            state.on_pubrec(&PubrecPacket::new(10)),
+            Err(QoS2Error::UnexpectedPacket {
+                state: QoS2State::Complete(10),
+                received: ControlType::PublishReceived,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_qos2_receiver() {
+        let packet = PublishPacket::new(
+            crate::TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level2(10),
+            "x",
+        );
+        let (mut state, pubrec) = QoS2State::from_publish(&packet).unwrap();
+        assert_eq!(pubrec.packet_identifier(), 10);
+        assert!(state.on_pubrec(&pubrec).is_err());
+
+        assert_eq!(state.on_pubrel(&PubrelPacket::new(10)), Ok(PubcompPacket::new(10)));
+        assert!(state.is_complete());
+        // Duplicate, the PUBCOMP is sent again
+        assert_eq!(state.on_pubrel(&PubrelPacket::new(10)), Ok(PubcompPacket::new(10)));
+        assert_eq!(state, QoS2State::Complete(10));
+
+        let packet = PublishPacket::new(
+            crate::TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level1(10),
+            "x",
+        );
+        assert_eq!(QoS2State::from_publish(&packet), None);
+    }
+}
@@ -0,0 +1,117 @@
+//! Packet writer shared between tasks
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::Mutex;
+
+use crate::io;
+use crate::packet::EncodablePacket;
+use crate::Encodable;
+
+/// Writes packets to an `AsyncWrite` from several tasks, e.g. a pinger and a publisher
+///
+/// Clones share the same writer. Each packet is encoded before taking the lock and is written
+/// whole, so packets sent concurrently are never interleaved on the wire.
+///
+/// ```rust
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// use mqtt::packet::{PingreqPacket, SharedPacketSink};
+///
+/// let sink = SharedPacketSink::new(Vec::new());
+/// let pinger = sink.clone();
+/// tokio::spawn(async move { pinger.send(&PingreqPacket::new()).await }).await??;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// This requires mqtt-rs to be built with `feature = "tokio"`
+#[derive(Debug)]
+pub struct SharedPacketSink<W> {
+    writer: Arc<Mutex<W>>,
+}
+
+impl<W: AsyncWrite + Unpin> SharedPacketSink<W> {
+    pub fn new(writer: W) -> SharedPacketSink<W> {
+        SharedPacketSink {
+            writer: Arc::new(Mutex::new(writer)),
+        }
+    }
+
+    /// Encodes `packet` and writes it, then flushes the writer
+    pub async fn send<P: EncodablePacket>(&self, packet: &P) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(packet.total_encoded_length() as usize);
+        packet.encode(&mut buf)?;
+
+        let mut writer = self.writer.lock().await;
+        writer.write_all(&buf).await?;
+        writer.flush().await
+    }
+}
+
+impl<W> Clone for SharedPacketSink<W> {
+    fn clone(&self) -> SharedPacketSink<W> {
+        SharedPacketSink {
+            writer: self.writer.clone(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    use crate::packet::{PingreqPacket, PublishPacket, QoSWithPacketIdentifier, VariablePacket};
+    use crate::TopicName;
+
+    #[tokio::test]
+    async fn test_shared_packet_sink() {
+        let (writer, reader) = tokio::io::duplex(16);
+        let sink = SharedPacketSink::new(writer);
+
+        let publish = PublishPacket::new(
+            TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level0,
+            vec![0xAA; 100],
+        );
+        let tasks = (0..4)
+            .map(|i| {
+                let sink = sink.clone();
+                let publish = publish.clone();
+                tokio::spawn(async move {
+                    for _ in 0..10 {
+                        if i % 2 == 0 {
+                            sink.send(&PingreqPacket::new()).await.unwrap();
+                        } else {
+                            sink.send(&publish).await.unwrap();
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let reader = tokio::spawn(async move {
+            let mut reader = reader;
+            let mut packets = Vec::new();
+            while let Ok(packet) = VariablePacket::parse(&mut reader).await {
+                packets.push(packet);
+            }
+            packets
+        });
+
+        for task in tasks {
+            task.await.unwrap();
+        }
+        drop(sink);
+
+        let packets = reader.await.unwrap();
+        assert_eq!(packets.len(), 40);
+        assert_eq!(packets.iter().filter(|packet| packet.is_publish()).count(), 20);
+        assert!(packets
+            .iter()
+            .filter_map(VariablePacket::as_publish)
+            .all(|packet| *packet == publish));
+    }
+}
@@ -0,0 +1,190 @@
+//! Packet reader and writer for blocking streams
+
+use alloc::vec::Vec;
+
+use crate::io::{self, Read, Write};
+use crate::packet::{packet_size, EncodablePacket, VariablePacket, VariablePacketError};
+use crate::Decodable;
+
+/// Size of each read from the underlying stream
+const READ_CHUNK_SIZE: usize = 4 * 1024;
+
+/// Reads packets from a blocking stream, such as a `std::net::TcpStream`
+///
+/// Data is read in chunks into an internal buffer, so decoding the fixed header does not issue
+/// a read for every byte.
+///
+/// ```rust
+/// use mqtt::packet::{PacketReader, PacketWriter, PingreqPacket, VariablePacket};
+///
+/// let mut writer = PacketWriter::new(Vec::new());
+/// writer.write_packet(&PingreqPacket::new()).unwrap();
+///
+/// let mut reader = PacketReader::new(&writer.get_ref()[..]);
+/// let packet = reader.read_packet().unwrap();
+/// assert!(matches!(packet, VariablePacket::PingreqPacket(..)));
+/// ```
+#[derive(Debug)]
+pub struct PacketReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    // Start of the data in `buf` that has not been decoded yet
+    pos: usize,
+}
+
+impl<R: Read> PacketReader<R> {
+    pub fn new(inner: R) -> PacketReader<R> {
+        PacketReader {
+            inner,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Reads the next packet, blocking until it has been received entirely
+    ///
+    /// An error is returned with `io::ErrorKind::UnexpectedEof` if the stream ends between packets,
+    /// and `VariablePacketError::IncompletePacket` if it ends in the middle of one.
+    pub fn read_packet(&mut self) -> Result<VariablePacket, VariablePacketError> {
+        loop {
+            let data = &self.buf[self.pos..];
+            if let Some(size) = packet_size(data).transpose()? {
+                if data.len() >= size {
+                    let packet = VariablePacket::decode(&mut &data[..size]);
+                    self.pos += size;
+                    return packet;
+                }
+            }
+
+            if self.fill_buf()? == 0 {
+                let data = &self.buf[self.pos..];
+                if data.is_empty() {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "stream ended").into());
+                }
+                let err = VariablePacket::decode(&mut &data[..]).err();
+                self.pos = self.buf.len();
+                return Err(err.unwrap_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof).into()));
+            }
+        }
+    }
+
+    /// Reads more data into the buffer, returns the number of bytes read
+    fn fill_buf(&mut self) -> io::Result<usize> {
+        // Move the undecoded data to the front before growing the buffer
+        self.buf.drain(..self.pos);
+        self.pos = 0;
+
+        let len = self.buf.len();
+        self.buf.resize(len + READ_CHUNK_SIZE, 0);
+        let result = loop {
+            match self.inner.read(&mut self.buf[len..]) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                result => break result,
+            }
+        };
+        self.buf.truncate(len + result.as_ref().map_or(0, |&n| n));
+        result
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying stream, data that has been read but not decoded yet is lost
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+/// Writes packets to a blocking stream, such as a `std::net::TcpStream`
+///
+/// Each packet is encoded into a reused buffer and written with a single `write_all`.
+#[derive(Debug)]
+pub struct PacketWriter<W> {
+    inner: W,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> PacketWriter<W> {
+    pub fn new(inner: W) -> PacketWriter<W> {
+        PacketWriter { inner, buf: Vec::new() }
+    }
+
+    /// Writes a whole packet and flushes the stream
+    pub fn write_packet<P: EncodablePacket>(&mut self, packet: &P) -> io::Result<()> {
+        self.buf.clear();
+        packet.encode_into(&mut self.buf)?;
+        self.inner.write_all(&self.buf)?;
+        self.inner.flush()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    use crate::packet::{PingreqPacket, PublishPacket, QoSWithPacketIdentifier};
+    use crate::TopicName;
+
+    /// Returns at most one byte per read
+    struct ByteReader<'a>(&'a [u8]);
+
+    impl Read for ByteReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.0.len().min(buf.len()).min(1);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_packet_reader_writer() {
+        let publish = PublishPacket::new(
+            TopicName::new("a/b").unwrap(),
+            QoSWithPacketIdentifier::Level1(10),
+            vec![b'x'; 200],
+        );
+
+        let mut writer = PacketWriter::new(Vec::new());
+        writer.write_packet(&publish).unwrap();
+        writer.write_packet(&PingreqPacket::new()).unwrap();
+        let buf = writer.into_inner();
+
+        let mut reader = PacketReader::new(ByteReader(&buf));
+        assert_eq!(reader.read_packet().unwrap(), VariablePacket::new(publish));
+        assert_eq!(reader.read_packet().unwrap(), VariablePacket::new(PingreqPacket::new()));
+        match reader.read_packet() {
+            Err(VariablePacketError::IoError(err)) => assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof),
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_packet_reader_incomplete() {
+        let mut reader = PacketReader::new(&b"\x30\x0A\x00\x03a/b"[..]);
+        match reader.read_packet() {
+            Err(VariablePacketError::IncompletePacket {
+                remaining_length: 10,
+                received: 5,
+            }) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+}
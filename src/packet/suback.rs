@@ -1,20 +1,22 @@
 //! SUBACK
 
-use std::cmp::Ordering;
-
-use std::io::{self, Read, Write};
-
-use byteorder::{ReadBytesExt, WriteBytesExt};
+use alloc::vec::{self, Vec};
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+use core::{fmt, slice};
 
 use crate::control::variable_header::PacketIdentifier;
 use crate::control::{ControlType, FixedHeader, PacketType};
+use crate::encodable::{ReadBytesExt, WriteBytesExt};
+use crate::io::{self, Read, Write};
 use crate::packet::{DecodablePacket, PacketError};
 use crate::qos::QualityOfService;
 use crate::{Decodable, Encodable};
 
 /// Subscribe code
 #[repr(u8)]
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SubscribeReturnCode {
     MaximumQoSLevel0 = 0x00,
     MaximumQoSLevel1 = 0x01,
@@ -22,6 +24,18 @@ pub enum SubscribeReturnCode {
     Failure = 0x80,
 }
 
+impl SubscribeReturnCode {
+    /// Maximum QoS granted by the server, `None` for `Failure`
+    pub fn granted_qos(self) -> Option<QualityOfService> {
+        match self {
+            SubscribeReturnCode::MaximumQoSLevel0 => Some(QualityOfService::Level0),
+            SubscribeReturnCode::MaximumQoSLevel1 => Some(QualityOfService::Level1),
+            SubscribeReturnCode::MaximumQoSLevel2 => Some(QualityOfService::Level2),
+            SubscribeReturnCode::Failure => None,
+        }
+    }
+}
+
 impl PartialOrd for SubscribeReturnCode {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         use self::SubscribeReturnCode::*;
@@ -39,6 +53,32 @@ impl PartialOrd for SubscribeReturnCode {
     }
 }
 
+/// `SubscribeReturnCode` with a total order, where `Failure` is smaller than any granted QoS
+///
+/// Unlike `SubscribeReturnCode` itself, it can be sorted or used with `max`.
+///
+/// ```rust
+/// use mqtt::packet::suback::{OrderedSubscribeReturnCode, SubscribeReturnCode};
+///
+/// let codes = [SubscribeReturnCode::MaximumQoSLevel1, SubscribeReturnCode::Failure];
+/// let max = codes.iter().copied().map(OrderedSubscribeReturnCode).max().unwrap();
+/// assert_eq!(max.0, SubscribeReturnCode::MaximumQoSLevel1);
+/// ```
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub struct OrderedSubscribeReturnCode(pub SubscribeReturnCode);
+
+impl Ord for OrderedSubscribeReturnCode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.granted_qos().cmp(&other.0.granted_qos())
+    }
+}
+
+impl PartialOrd for OrderedSubscribeReturnCode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl From<QualityOfService> for SubscribeReturnCode {
     fn from(qos: QualityOfService) -> Self {
         match qos {
@@ -50,7 +90,12 @@ impl From<QualityOfService> for SubscribeReturnCode {
 }
 
 /// `SUBACK` packet
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(remote = "Self")
+)]
 pub struct SubackPacket {
     fixed_header: FixedHeader,
     packet_identifier: PacketIdentifier,
@@ -58,6 +103,7 @@ pub struct SubackPacket {
 }
 
 encodable_packet!(SubackPacket(packet_identifier, payload));
+serde_packet!(SubackPacket, SubscribeAcknowledgement);
 
 impl SubackPacket {
     pub fn new(pkid: u16, subscribes: Vec<SubscribeReturnCode>) -> SubackPacket {
@@ -81,6 +127,46 @@ impl SubackPacket {
     pub fn subscribes(&self) -> &[SubscribeReturnCode] {
         &self.payload.subscribes[..]
     }
+
+    /// Number of return codes, one for each topic filter of the `SUBSCRIBE`
+    pub fn return_code_count(&self) -> usize {
+        self.payload.subscribes.len()
+    }
+}
+
+/// Consumes the packet, yielding the return codes in the order of the `SUBSCRIBE` topic filters
+impl IntoIterator for SubackPacket {
+    type Item = SubscribeReturnCode;
+    type IntoIter = vec::IntoIter<SubscribeReturnCode>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.payload.subscribes.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a SubackPacket {
+    type Item = &'a SubscribeReturnCode;
+    type IntoIter = slice::Iter<'a, SubscribeReturnCode>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.payload.subscribes.iter()
+    }
+}
+
+impl fmt::Display for SubackPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SUBACK id={} [", self.packet_identifier.0)?;
+        for (i, code) in self.payload.subscribes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            match *code {
+                SubscribeReturnCode::Failure => f.write_str("Failure")?,
+                code => write!(f, "QoS{}", code as u8)?,
+            }
+        }
+        f.write_str("]")
+    }
 }
 
 impl DecodablePacket for SubackPacket {
@@ -88,11 +174,15 @@ impl DecodablePacket for SubackPacket {
 
     fn decode_packet<R: Read>(reader: &mut R, fixed_header: FixedHeader) -> Result<Self, PacketError<Self>> {
         let packet_identifier = PacketIdentifier::decode(reader)?;
-        let payload: SubackPacketPayload = SubackPacketPayload::decode_with(
-            reader,
-            fixed_header.remaining_length - packet_identifier.encoded_length(),
-        )
-        .map_err(PacketError::PayloadError)?;
+        let payload_len = fixed_header
+            .remaining_length
+            .checked_sub(packet_identifier.encoded_length())
+            .ok_or(PacketError::PayloadError(SubackPacketError::RemainingLengthTooSmall {
+                remaining_length: fixed_header.remaining_length,
+                header_length: packet_identifier.encoded_length(),
+            }))?;
+        let payload: SubackPacketPayload =
+            SubackPacketPayload::decode_with(reader, payload_len).map_err(PacketError::PayloadError)?;
         Ok(SubackPacket {
             fixed_header,
             packet_identifier,
@@ -101,7 +191,8 @@ impl DecodablePacket for SubackPacket {
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct SubackPacketPayload {
     subscribes: Vec<SubscribeReturnCode>,
 }
@@ -122,7 +213,7 @@ impl Encodable for SubackPacketPayload {
     }
 
     fn encoded_length(&self) -> u32 {
-        self.subscribes.len() as u32
+        u32::try_from(self.subscribes.len()).unwrap_or(u32::MAX)
     }
 }
 
@@ -155,4 +246,39 @@ pub enum SubackPacketError {
     IoError(#[from] io::Error),
     #[error("invalid subscribe return code {0}")]
     InvalidSubscribeReturnCode(u8),
+    #[error("remaining length {remaining_length} is smaller than variable header length {header_length}")]
+    RemainingLengthTooSmall { remaining_length: u32, header_length: u32 },
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_subscribe_return_code_order() {
+        use self::SubscribeReturnCode::*;
+
+        let mut codes: Vec<_> = [MaximumQoSLevel2, Failure, MaximumQoSLevel0, MaximumQoSLevel1]
+            .iter()
+            .copied()
+            .map(OrderedSubscribeReturnCode)
+            .collect();
+        codes.sort();
+        let codes: Vec<_> = codes.into_iter().map(|c| c.0).collect();
+        assert_eq!(codes, [Failure, MaximumQoSLevel0, MaximumQoSLevel1, MaximumQoSLevel2]);
+
+        assert_eq!(Failure.granted_qos(), None);
+        assert_eq!(MaximumQoSLevel1.granted_qos(), Some(QualityOfService::Level1));
+    }
+
+    #[test]
+    fn test_suback_packet_iter() {
+        use self::SubscribeReturnCode::*;
+
+        let packet = SubackPacket::new(1, vec![MaximumQoSLevel1, Failure]);
+        assert_eq!(packet.return_code_count(), 2);
+        let granted: Vec<_> = (&packet).into_iter().map(|code| code.granted_qos()).collect();
+        assert_eq!(granted, [Some(QualityOfService::Level1), None]);
+        assert_eq!(packet.into_iter().collect::<Vec<_>>(), [MaximumQoSLevel1, Failure]);
+    }
 }
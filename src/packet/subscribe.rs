@@ -1,18 +1,24 @@
 //! SUBSCRIBE
 
-use std::io::{self, Read, Write};
-use std::string::FromUtf8Error;
-
-use byteorder::{ReadBytesExt, WriteBytesExt};
+use alloc::string::FromUtf8Error;
+use alloc::vec::{self, Vec};
+use core::{fmt, slice};
 
 use crate::control::variable_header::PacketIdentifier;
 use crate::control::{ControlType, FixedHeader, PacketType};
-use crate::packet::{DecodablePacket, PacketError};
-use crate::topic_filter::{TopicFilter, TopicFilterDecodeError, TopicFilterError};
+use crate::encodable::{ReadBytesExt, WriteBytesExt};
+use crate::io::{self, Read, Write};
+use crate::packet::{DecodablePacket, EncodablePacket, PacketError};
+use crate::topic_filter::{TopicFilter, TopicFilterDecodeError, TopicFilterError, TopicFilterRef};
 use crate::{Decodable, Encodable, QualityOfService};
 
 /// `SUBSCRIBE` packet
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(remote = "Self")
+)]
 pub struct SubscribePacket {
     fixed_header: FixedHeader,
     packet_identifier: PacketIdentifier,
@@ -20,9 +26,14 @@ pub struct SubscribePacket {
 }
 
 encodable_packet!(SubscribePacket(packet_identifier, payload));
+serde_packet!(SubscribePacket, Subscribe);
 
 impl SubscribePacket {
     pub fn new(pkid: u16, subscribes: Vec<(TopicFilter, QualityOfService)>) -> SubscribePacket {
+        debug_assert!(
+            !subscribes.is_empty(),
+            "SUBSCRIBE must contain at least one topic filter"
+        );
         let mut pk = SubscribePacket {
             fixed_header: FixedHeader::new(PacketType::with_default(ControlType::Subscribe), 0),
             packet_identifier: PacketIdentifier(pkid),
@@ -32,6 +43,45 @@ impl SubscribePacket {
         pk
     }
 
+    /// Creates a `SUBSCRIBE` packet from any collection of subscriptions
+    pub fn from_filters<I>(pkid: u16, subscribes: I) -> SubscribePacket
+    where
+        I: IntoIterator<Item = (TopicFilter, QualityOfService)>,
+    {
+        SubscribePacket::new(pkid, subscribes.into_iter().collect())
+    }
+
+    /// Creates a `SUBSCRIBE` packet from topic filter strings, validating each of them
+    ///
+    /// Fails with `EmptySubscription` if there is no topic filter at all [MQTT-3.8.3-3].
+    ///
+    /// ```rust
+    /// use mqtt::packet::SubscribePacket;
+    /// use mqtt::QualityOfService;
+    ///
+    /// let packet = SubscribePacket::try_from_filters(
+    ///     10,
+    ///     [("sport/+/player1", QualityOfService::Level1), ("news/#", QualityOfService::Level0)],
+    /// )
+    /// .unwrap();
+    /// assert_eq!(packet.subscribes().len(), 2);
+    ///
+    /// assert!(SubscribePacket::try_from_filters(10, [("news/#/sport", QualityOfService::Level0)]).is_err());
+    /// ```
+    pub fn try_from_filters<'a, I>(pkid: u16, subscribes: I) -> Result<SubscribePacket, SubscribePacketError>
+    where
+        I: IntoIterator<Item = (&'a str, QualityOfService)>,
+    {
+        let subscribes = subscribes
+            .into_iter()
+            .map(|(filter, qos)| Ok((TopicFilter::new(filter)?, qos)))
+            .collect::<Result<Vec<_>, TopicFilterError>>()?;
+        if subscribes.is_empty() {
+            return Err(SubscribePacketError::EmptySubscription);
+        }
+        Ok(SubscribePacket::new(pkid, subscribes))
+    }
+
     pub fn packet_identifier(&self) -> u16 {
         self.packet_identifier.0
     }
@@ -43,6 +93,49 @@ impl SubscribePacket {
     pub fn subscribes(&self) -> &[(TopicFilter, QualityOfService)] {
         &self.payload.subscribes[..]
     }
+
+    /// Appends a subscription, to batch several of them in one packet
+    pub fn push(&mut self, filter: TopicFilter, qos: QualityOfService) {
+        self.payload.subscribes.push((filter, qos));
+        self.fix_header_remaining_len();
+    }
+
+    /// Number of topic filters subscribed to
+    pub fn filter_count(&self) -> usize {
+        self.payload.subscribes.len()
+    }
+}
+
+/// Consumes the packet, yielding the subscriptions in order
+impl IntoIterator for SubscribePacket {
+    type Item = (TopicFilter, QualityOfService);
+    type IntoIter = vec::IntoIter<(TopicFilter, QualityOfService)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.payload.subscribes.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a SubscribePacket {
+    type Item = &'a (TopicFilter, QualityOfService);
+    type IntoIter = slice::Iter<'a, (TopicFilter, QualityOfService)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.payload.subscribes.iter()
+    }
+}
+
+impl fmt::Display for SubscribePacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SUBSCRIBE id={} [", self.packet_identifier.0)?;
+        for (i, (filter, qos)) in self.payload.subscribes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}:QoS{}", &filter[..], *qos as u8)?;
+        }
+        f.write_str("]")
+    }
 }
 
 impl DecodablePacket for SubscribePacket {
@@ -50,11 +143,17 @@ impl DecodablePacket for SubscribePacket {
 
     fn decode_packet<R: Read>(reader: &mut R, fixed_header: FixedHeader) -> Result<Self, PacketError<Self>> {
         let packet_identifier: PacketIdentifier = PacketIdentifier::decode(reader)?;
-        let payload: SubscribePacketPayload = SubscribePacketPayload::decode_with(
-            reader,
-            fixed_header.remaining_length - packet_identifier.encoded_length(),
-        )
-        .map_err(PacketError::PayloadError)?;
+        let payload_len = fixed_header
+            .remaining_length
+            .checked_sub(packet_identifier.encoded_length())
+            .ok_or(PacketError::PayloadError(
+                SubscribePacketError::RemainingLengthTooSmall {
+                    remaining_length: fixed_header.remaining_length,
+                    header_length: packet_identifier.encoded_length(),
+                },
+            ))?;
+        let payload: SubscribePacketPayload =
+            SubscribePacketPayload::decode_with(reader, payload_len).map_err(PacketError::PayloadError)?;
         Ok(SubscribePacket {
             fixed_header,
             packet_identifier,
@@ -64,7 +163,8 @@ impl DecodablePacket for SubscribePacket {
 }
 
 /// Payload of subscribe packet
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct SubscribePacketPayload {
     subscribes: Vec<(TopicFilter, QualityOfService)>,
 }
@@ -77,7 +177,7 @@ impl SubscribePacketPayload {
 
 impl Encodable for SubscribePacketPayload {
     fn encode<W: Write>(&self, writer: &mut W) -> Result<(), io::Error> {
-        for &(ref filter, ref qos) in self.subscribes.iter() {
+        for (filter, qos) in self.subscribes.iter() {
             filter.encode(writer)?;
             writer.write_u8(*qos as u8)?;
         }
@@ -86,7 +186,9 @@ impl Encodable for SubscribePacketPayload {
     }
 
     fn encoded_length(&self) -> u32 {
-        self.subscribes.iter().fold(0, |b, a| b + a.0.encoded_length() + 1)
+        self.subscribes
+            .iter()
+            .fold(0u32, |b, a| b.saturating_add(a.0.encoded_length() + 1))
     }
 }
 
@@ -102,17 +204,20 @@ impl Decodable for SubscribePacketPayload {
 
         while payload_len > 0 {
             let filter = TopicFilter::decode(reader)?;
-            let qos = match reader.read_u8()? {
-                0 => QualityOfService::Level0,
-                1 => QualityOfService::Level1,
-                2 => QualityOfService::Level2,
-                _ => return Err(SubscribePacketError::InvalidQualityOfService),
-            };
-
-            payload_len -= filter.encoded_length() + 1;
+            let qos =
+                QualityOfService::from_u8(reader.read_u8()?).ok_or(SubscribePacketError::InvalidQualityOfService)?;
+
+            payload_len = payload_len
+                .checked_sub(filter.encoded_length() + 1)
+                .ok_or(SubscribePacketError::PayloadOverrun)?;
             subs.push((filter, qos));
         }
 
+        // The payload must contain at least one topic filter [MQTT-3.8.3-3]
+        if subs.is_empty() {
+            return Err(SubscribePacketError::EmptySubscription);
+        }
+
         Ok(SubscribePacketPayload::new(subs))
     }
 }
@@ -127,6 +232,12 @@ pub enum SubscribePacketError {
     InvalidQualityOfService,
     #[error(transparent)]
     TopicFilterError(#[from] TopicFilterError),
+    #[error("no topic filter in subscription")]
+    EmptySubscription,
+    #[error("subscription exceeds the remaining payload length")]
+    PayloadOverrun,
+    #[error("remaining length {remaining_length} is smaller than variable header length {header_length}")]
+    RemainingLengthTooSmall { remaining_length: u32, header_length: u32 },
 }
 
 impl From<TopicFilterDecodeError> for SubscribePacketError {
@@ -137,3 +248,148 @@ impl From<TopicFilterDecodeError> for SubscribePacketError {
         }
     }
 }
+
+/// `SUBSCRIBE` packet by reference, for encoding only
+pub struct SubscribePacketRef<'a> {
+    fixed_header: FixedHeader,
+    packet_identifier: PacketIdentifier,
+    subscribes: &'a [(&'a TopicFilterRef, QualityOfService)],
+}
+
+impl<'a> SubscribePacketRef<'a> {
+    pub fn new(pkid: u16, subscribes: &'a [(&'a TopicFilterRef, QualityOfService)]) -> SubscribePacketRef<'a> {
+        debug_assert!(
+            !subscribes.is_empty(),
+            "SUBSCRIBE must contain at least one topic filter"
+        );
+        let mut pk = SubscribePacketRef {
+            fixed_header: FixedHeader::new(PacketType::with_default(ControlType::Subscribe), 0),
+            packet_identifier: PacketIdentifier(pkid),
+            subscribes,
+        };
+        pk.fixed_header.remaining_length = pk.encoded_packet_length();
+        pk
+    }
+}
+
+impl EncodablePacket for SubscribePacketRef<'_> {
+    fn fixed_header(&self) -> &FixedHeader {
+        &self.fixed_header
+    }
+
+    fn encode_packet<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.packet_identifier.encode(writer)?;
+        for (filter, qos) in self.subscribes.iter() {
+            filter.encode(writer)?;
+            writer.write_u8(*qos as u8)?;
+        }
+        Ok(())
+    }
+
+    fn encoded_packet_length(&self) -> u32 {
+        self.subscribes
+            .iter()
+            .fold(self.packet_identifier.encoded_length(), |b, a| {
+                b.saturating_add(a.0.encoded_length() + 1)
+            })
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use crate::control::variable_header::VariableHeaderError;
+
+    #[test]
+    fn test_subscribe_packet_empty() {
+        let mut decode_buf = Cursor::new(&b"\x82\x02\x00\x01"[..]);
+        match SubscribePacket::decode(&mut decode_buf) {
+            Err(PacketError::PayloadError(SubscribePacketError::EmptySubscription)) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_subscribe_packet_try_from_filters_empty() {
+        match SubscribePacket::try_from_filters(10, Vec::<(&str, QualityOfService)>::new()) {
+            Err(SubscribePacketError::EmptySubscription) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+        match SubscribePacket::try_from_filters(10, [("a/#/b", QualityOfService::Level0)]) {
+            Err(SubscribePacketError::TopicFilterError(..)) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_subscribe_packet_zero_packet_identifier() {
+        let mut decode_buf = Cursor::new(&b"\x82\x06\x00\x00\x00\x01a\x00"[..]);
+        match SubscribePacket::decode(&mut decode_buf) {
+            Err(PacketError::VariableHeaderError(VariableHeaderError::ZeroPacketIdentifier(..))) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        let mut decode_buf = Cursor::new(&b"\x82\x06\x00\x01\x00\x01a\x00"[..]);
+        let packet = SubscribePacket::decode(&mut decode_buf).unwrap();
+        assert_eq!(packet.filter_count(), 1);
+        for (filter, qos) in &packet {
+            assert_eq!(&filter[..], "a");
+            assert_eq!(*qos, QualityOfService::Level0);
+        }
+        let subscribes: Vec<_> = packet.into_iter().collect();
+        assert_eq!(subscribes, [(TopicFilter::new("a").unwrap(), QualityOfService::Level0)]);
+    }
+
+    #[test]
+    fn test_subscribe_packet_push() {
+        let mut packet = SubscribePacket::new(1, vec![(TopicFilter::new("a").unwrap(), QualityOfService::Level0)]);
+        packet.push(TopicFilter::new("b/#").unwrap(), QualityOfService::Level2);
+        assert_eq!(packet.filter_count(), 2);
+
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+        assert_eq!(&buf[..], b"\x82\x0C\x00\x01\x00\x01a\x00\x00\x03b/#\x02");
+        assert_eq!(SubscribePacket::decode(&mut Cursor::new(buf)).unwrap(), packet);
+    }
+
+    #[test]
+    fn test_subscribe_packet_remaining_length_too_small() {
+        let fixed_header = FixedHeader::new(PacketType::with_default(ControlType::Subscribe), 1);
+        let mut decode_buf = Cursor::new(&b"\x00\x01"[..]);
+        match SubscribePacket::decode_packet(&mut decode_buf, fixed_header) {
+            Err(PacketError::PayloadError(SubscribePacketError::RemainingLengthTooSmall { .. })) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_subscribe_packet_truncated_payload() {
+        // Declares a 2 bytes payload, but the subscription takes 4
+        let fixed_header = FixedHeader::new(PacketType::with_default(ControlType::Subscribe), 4);
+        let mut decode_buf = Cursor::new(&b"\x00\x01\x00\x01a\x00"[..]);
+        match SubscribePacket::decode_packet(&mut decode_buf, fixed_header) {
+            Err(PacketError::PayloadError(SubscribePacketError::PayloadOverrun)) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_subscribe_packet_ref() {
+        let filter = TopicFilterRef::new("a/+").unwrap();
+        let subscribes = [(filter, QualityOfService::Level1)];
+        let packet = SubscribePacketRef::new(10, &subscribes);
+
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+
+        let mut decode_buf = Cursor::new(buf);
+        let decoded = SubscribePacket::decode(&mut decode_buf).unwrap();
+        assert_eq!(
+            decoded,
+            SubscribePacket::new(10, vec![(TopicFilter::new("a/+").unwrap(), QualityOfService::Level1)])
+        );
+    }
+}
@@ -0,0 +1,221 @@
+//! Utilities for testing peers against malformed packets, and sample packets for benchmarks
+//!
+//! This requires mqtt-rs to be built with `feature = "test-util"`, or its alias `feature = "testing"`
+
+use alloc::{borrow::ToOwned, vec, vec::Vec};
+
+use crate::control::ConnectReturnCode;
+use crate::io::{self, Write};
+use crate::packet::suback::SubscribeReturnCode;
+use crate::packet::*;
+use crate::{QualityOfService, TopicFilter, TopicName};
+
+/// One packet of each type with representative content, as a corpus for benchmarks and snapshot
+/// tests of the encoding and decoding paths
+///
+/// The packets are valid and always the same, so they round-trip through encoding and decoding.
+///
+/// ```rust
+/// use mqtt::packet::{sample_packets, VariablePacket};
+///
+/// let mut buf = Vec::new();
+/// for packet in sample_packets() {
+///     buf.extend(packet.encode_to_vec().unwrap());
+/// }
+/// assert_eq!(VariablePacket::decode_all(&buf).unwrap(), sample_packets());
+/// ```
+pub fn sample_packets() -> Vec<VariablePacket> {
+    let mut connect = ConnectPacket::new("sample-client");
+    connect.set_keep_alive(60);
+    connect.set_clean_session(true);
+    connect.set_user_name(Some("user".to_owned()));
+    connect.set_password(Some("password".to_owned()));
+    connect.set_will_message(Some(Will {
+        topic: TopicName::new("clients/sample-client").unwrap(),
+        payload: b"offline".to_vec(),
+        qos: QualityOfService::Level1,
+        retain: false,
+    }));
+
+    let mut publish = PublishPacket::new(
+        TopicName::new("sensors/room-1/temperature").unwrap(),
+        QoSWithPacketIdentifier::Level1(1),
+        &br#"{"value":21.5,"unit":"C"}"#[..],
+    );
+    publish.set_retain(true);
+
+    vec![
+        connect.into(),
+        ConnackPacket::new(false, ConnectReturnCode::ConnectionAccepted).into(),
+        publish.into(),
+        PubackPacket::new(1).into(),
+        PubrecPacket::new(2).into(),
+        PubrelPacket::new(2).into(),
+        PubcompPacket::new(2).into(),
+        SubscribePacket::new(
+            3,
+            vec![
+                (
+                    TopicFilter::new("sensors/+/temperature").unwrap(),
+                    QualityOfService::Level1,
+                ),
+                (TopicFilter::new("alerts/#").unwrap(), QualityOfService::Level2),
+            ],
+        )
+        .into(),
+        SubackPacket::new(
+            3,
+            vec![SubscribeReturnCode::MaximumQoSLevel1, SubscribeReturnCode::Failure],
+        )
+        .into(),
+        UnsubscribePacket::new(4, vec![TopicFilter::new("alerts/#").unwrap()]).into(),
+        UnsubackPacket::new(4).into(),
+        PingreqPacket::new().into(),
+        PingrespPacket::new().into(),
+        DisconnectPacket::new().into(),
+    ]
+}
+
+/// Builds raw packet bytes that are written verbatim, without any validation
+///
+/// ```rust
+/// use mqtt::packet::test_support::MalformedPacketBuilder;
+///
+/// // PUBREL with reserved flags cleared
+/// let bytes = MalformedPacketBuilder::new(0x60).body(vec![0x00, 0x01]).build();
+/// assert_eq!(bytes, [0x60, 0x02, 0x00, 0x01]);
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MalformedPacketBuilder {
+    first_byte: u8,
+    remaining_length: Option<Vec<u8>>,
+    body: Vec<u8>,
+}
+
+impl MalformedPacketBuilder {
+    /// Creates a builder with the first byte of the fixed header, i.e. the packet type and flags
+    pub fn new(first_byte: u8) -> MalformedPacketBuilder {
+        MalformedPacketBuilder {
+            first_byte,
+            remaining_length: None,
+            body: Vec::new(),
+        }
+    }
+
+    /// Sets the first byte of the fixed header
+    pub fn first_byte(mut self, first_byte: u8) -> MalformedPacketBuilder {
+        self.first_byte = first_byte;
+        self
+    }
+
+    /// Encodes `length` as the remaining length, regardless of the actual body length
+    pub fn remaining_length(mut self, length: u32) -> MalformedPacketBuilder {
+        self.remaining_length = Some(encode_remaining_length(length));
+        self
+    }
+
+    /// Sets the raw bytes of the remaining length field
+    pub fn remaining_length_bytes<B: Into<Vec<u8>>>(mut self, bytes: B) -> MalformedPacketBuilder {
+        self.remaining_length = Some(bytes.into());
+        self
+    }
+
+    /// Sets data after the fixed header
+    ///
+    /// If the remaining length is not set explicitly, it is derived from the length of `body`.
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> MalformedPacketBuilder {
+        self.body = body.into();
+        self
+    }
+
+    /// Writes the packet bytes to `writer`
+    pub fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&[self.first_byte])?;
+        match self.remaining_length {
+            Some(ref bytes) => writer.write_all(bytes)?,
+            None => writer.write_all(&encode_remaining_length(self.body.len() as u32))?,
+        }
+        writer.write_all(&self.body)
+    }
+
+    /// Returns the packet bytes
+    pub fn build(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode(&mut buf).expect("writing to Vec never fails");
+        buf
+    }
+}
+
+fn encode_remaining_length(mut length: u32) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(4);
+    loop {
+        let mut byte = (length & 0x7F) as u8;
+        length >>= 7;
+
+        if length > 0 {
+            byte |= 0x80;
+        }
+
+        bytes.push(byte);
+
+        if length == 0 {
+            break;
+        }
+    }
+    bytes
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use crate::control::fixed_header::FixedHeaderError;
+    use crate::control::packet_type::PacketTypeError;
+    use crate::packet::{VariablePacket, VariablePacketError};
+    use crate::Decodable;
+
+    #[test]
+    fn test_sample_packets() {
+        use crate::control::ControlType;
+
+        let packets = sample_packets();
+        let types = packets.iter().map(VariablePacket::control_type).collect::<Vec<_>>();
+        assert_eq!(types.len(), 14);
+        assert!(!types.contains(&ControlType::Auth));
+        for (i, t) in types.iter().enumerate() {
+            assert!(!types[i + 1..].contains(t), "{:?} appears twice", t);
+        }
+
+        for packet in packets {
+            let buf = packet.encode_to_vec().unwrap();
+            assert_eq!(VariablePacket::decode(&mut Cursor::new(buf)).unwrap(), packet);
+        }
+    }
+
+    #[test]
+    fn test_malformed_pubrel_flags() {
+        let bytes = MalformedPacketBuilder::new(0x60).body(vec![0x00, 0x0A]).build();
+        assert_eq!(bytes, [0x60, 0x02, 0x00, 0x0A]);
+
+        let mut buf = Cursor::new(bytes);
+        match VariablePacket::decode(&mut buf) {
+            Err(VariablePacketError::FixedHeaderError(FixedHeaderError::PacketTypeError(
+                PacketTypeError::InvalidFlag(..),
+            ))) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_malformed_remaining_length() {
+        let bytes = MalformedPacketBuilder::new(0xC0)
+            .remaining_length_bytes(vec![0x80, 0x80, 0x80, 0x80, 0x01])
+            .build();
+        assert_eq!(bytes, [0xC0, 0x80, 0x80, 0x80, 0x80, 0x01]);
+
+        let bytes = MalformedPacketBuilder::new(0xC0).remaining_length(321).build();
+        assert_eq!(bytes, [0xC0, 0xC1, 0x02]);
+    }
+}
@@ -1,20 +1,27 @@
 //! UNSUBACK
 
-use std::io::Read;
+use core::fmt;
 
 use crate::control::variable_header::PacketIdentifier;
 use crate::control::{ControlType, FixedHeader, PacketType};
+use crate::io::Read;
 use crate::packet::{DecodablePacket, PacketError};
 use crate::Decodable;
 
 /// `UNSUBACK` packet
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(remote = "Self")
+)]
 pub struct UnsubackPacket {
     fixed_header: FixedHeader,
     packet_identifier: PacketIdentifier,
 }
 
 encodable_packet!(UnsubackPacket(packet_identifier));
+serde_packet!(UnsubackPacket, UnsubscribeAcknowledgement);
 
 impl UnsubackPacket {
     pub fn new(pkid: u16) -> UnsubackPacket {
@@ -33,8 +40,14 @@ impl UnsubackPacket {
     }
 }
 
+impl fmt::Display for UnsubackPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UNSUBACK id={}", self.packet_identifier.0)
+    }
+}
+
 impl DecodablePacket for UnsubackPacket {
-    type DecodePacketError = std::convert::Infallible;
+    type DecodePacketError = core::convert::Infallible;
 
     fn decode_packet<R: Read>(reader: &mut R, fixed_header: FixedHeader) -> Result<Self, PacketError<Self>> {
         let packet_identifier: PacketIdentifier = PacketIdentifier::decode(reader)?;
@@ -1,16 +1,23 @@
 //! UNSUBSCRIBE
 
-use std::io::{self, Read, Write};
-use std::string::FromUtf8Error;
+use alloc::string::FromUtf8Error;
+use alloc::vec::Vec;
+use core::fmt;
 
 use crate::control::variable_header::PacketIdentifier;
 use crate::control::{ControlType, FixedHeader, PacketType};
+use crate::io::{self, Read, Write};
 use crate::packet::{DecodablePacket, PacketError};
 use crate::topic_filter::{TopicFilter, TopicFilterDecodeError, TopicFilterError};
 use crate::{Decodable, Encodable};
 
 /// `UNSUBSCRIBE` packet
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(remote = "Self")
+)]
 pub struct UnsubscribePacket {
     fixed_header: FixedHeader,
     packet_identifier: PacketIdentifier,
@@ -18,9 +25,14 @@ pub struct UnsubscribePacket {
 }
 
 encodable_packet!(UnsubscribePacket(packet_identifier, payload));
+serde_packet!(UnsubscribePacket, Unsubscribe);
 
 impl UnsubscribePacket {
     pub fn new(pkid: u16, subscribes: Vec<TopicFilter>) -> UnsubscribePacket {
+        debug_assert!(
+            !subscribes.is_empty(),
+            "UNSUBSCRIBE must contain at least one topic filter"
+        );
         let mut pk = UnsubscribePacket {
             fixed_header: FixedHeader::new(PacketType::with_default(ControlType::Unsubscribe), 0),
             packet_identifier: PacketIdentifier(pkid),
@@ -41,6 +53,25 @@ impl UnsubscribePacket {
     pub fn subscribes(&self) -> &[TopicFilter] {
         &self.payload.subscribes[..]
     }
+
+    /// Appends a topic filter to unsubscribe from
+    pub fn push(&mut self, filter: TopicFilter) {
+        self.payload.subscribes.push(filter);
+        self.fix_header_remaining_len();
+    }
+}
+
+impl fmt::Display for UnsubscribePacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UNSUBSCRIBE id={} [", self.packet_identifier.0)?;
+        for (i, filter) in self.payload.subscribes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(&filter[..])?;
+        }
+        f.write_str("]")
+    }
 }
 
 impl DecodablePacket for UnsubscribePacket {
@@ -48,11 +79,17 @@ impl DecodablePacket for UnsubscribePacket {
 
     fn decode_packet<R: Read>(reader: &mut R, fixed_header: FixedHeader) -> Result<Self, PacketError<Self>> {
         let packet_identifier: PacketIdentifier = PacketIdentifier::decode(reader)?;
-        let payload: UnsubscribePacketPayload = UnsubscribePacketPayload::decode_with(
-            reader,
-            fixed_header.remaining_length - packet_identifier.encoded_length(),
-        )
-        .map_err(PacketError::PayloadError)?;
+        let payload_len = fixed_header
+            .remaining_length
+            .checked_sub(packet_identifier.encoded_length())
+            .ok_or(PacketError::PayloadError(
+                UnsubscribePacketError::RemainingLengthTooSmall {
+                    remaining_length: fixed_header.remaining_length,
+                    header_length: packet_identifier.encoded_length(),
+                },
+            ))?;
+        let payload: UnsubscribePacketPayload =
+            UnsubscribePacketPayload::decode_with(reader, payload_len).map_err(PacketError::PayloadError)?;
         Ok(UnsubscribePacket {
             fixed_header,
             packet_identifier,
@@ -61,7 +98,8 @@ impl DecodablePacket for UnsubscribePacket {
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct UnsubscribePacketPayload {
     subscribes: Vec<TopicFilter>,
 }
@@ -82,7 +120,9 @@ impl Encodable for UnsubscribePacketPayload {
     }
 
     fn encoded_length(&self) -> u32 {
-        self.subscribes.iter().fold(0, |b, a| b + a.encoded_length())
+        self.subscribes
+            .iter()
+            .fold(0u32, |b, a| b.saturating_add(a.encoded_length()))
     }
 }
 
@@ -98,20 +138,35 @@ impl Decodable for UnsubscribePacketPayload {
 
         while payload_len > 0 {
             let filter = TopicFilter::decode(reader)?;
-            payload_len -= filter.encoded_length();
+            payload_len = payload_len
+                .checked_sub(filter.encoded_length())
+                .ok_or(UnsubscribePacketError::PayloadOverrun)?;
             subs.push(filter);
         }
 
+        // The payload must contain at least one topic filter [MQTT-3.10.3-2]
+        if subs.is_empty() {
+            return Err(UnsubscribePacketError::EmptySubscription);
+        }
+
         Ok(UnsubscribePacketPayload::new(subs))
     }
 }
 
 #[derive(Debug, thiserror::Error)]
-#[error(transparent)]
 pub enum UnsubscribePacketError {
+    #[error(transparent)]
     IoError(#[from] io::Error),
+    #[error(transparent)]
     FromUtf8Error(#[from] FromUtf8Error),
+    #[error(transparent)]
     TopicFilterError(#[from] TopicFilterError),
+    #[error("no topic filter in subscription")]
+    EmptySubscription,
+    #[error("topic filter exceeds the remaining payload length")]
+    PayloadOverrun,
+    #[error("remaining length {remaining_length} is smaller than variable header length {header_length}")]
+    RemainingLengthTooSmall { remaining_length: u32, header_length: u32 },
 }
 
 impl From<TopicFilterDecodeError> for UnsubscribePacketError {
@@ -122,3 +177,40 @@ impl From<TopicFilterDecodeError> for UnsubscribePacketError {
         }
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    use std::io::Cursor;
+
+    #[test]
+    fn test_unsubscribe_packet_empty() {
+        let mut decode_buf = Cursor::new(&b"\xA2\x02\x00\x01"[..]);
+        match UnsubscribePacket::decode(&mut decode_buf) {
+            Err(PacketError::PayloadError(UnsubscribePacketError::EmptySubscription)) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_unsubscribe_packet_push() {
+        let mut packet = UnsubscribePacket::new(1, vec![TopicFilter::new("a").unwrap()]);
+        packet.push(TopicFilter::new("b/#").unwrap());
+
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+        assert_eq!(&buf[..], b"\xA2\x0A\x00\x01\x00\x01a\x00\x03b/#");
+        assert_eq!(UnsubscribePacket::decode(&mut Cursor::new(buf)).unwrap(), packet);
+    }
+
+    #[test]
+    fn test_unsubscribe_packet_truncated_payload() {
+        let fixed_header = FixedHeader::new(PacketType::with_default(ControlType::Unsubscribe), 4);
+        let mut decode_buf = Cursor::new(&b"\x00\x01\x00\x01a"[..]);
+        match UnsubscribePacket::decode_packet(&mut decode_buf, fixed_header) {
+            Err(PacketError::PayloadError(UnsubscribePacketError::PayloadOverrun)) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+}
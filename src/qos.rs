@@ -3,13 +3,36 @@
 use crate::packet::publish::QoSWithPacketIdentifier;
 
 #[repr(u8)]
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Ord, PartialOrd, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum QualityOfService {
     Level0 = 0,
     Level1 = 1,
     Level2 = 2,
 }
 
+impl QualityOfService {
+    /// Converts from the 2 bits QoS value, returns `None` for the reserved value 3 and above
+    pub fn from_u8(val: u8) -> Option<QualityOfService> {
+        match val {
+            0 => Some(QualityOfService::Level0),
+            1 => Some(QualityOfService::Level1),
+            2 => Some(QualityOfService::Level2),
+            _ => None,
+        }
+    }
+
+    /// QoS of a message delivered to a subscriber that has been `granted` this QoS level
+    ///
+    /// A message is delivered at the minimum of the QoS it was published with and the QoS
+    /// granted to the subscription, so a QoS 2 message forwarded to a QoS 1 subscription
+    /// is sent with QoS 1. [MQTT-3.8.4-6]
+    #[inline]
+    pub fn downgrade(self, granted: QualityOfService) -> QualityOfService {
+        core::cmp::min(self, granted)
+    }
+}
+
 impl From<QoSWithPacketIdentifier> for QualityOfService {
     fn from(qos: QoSWithPacketIdentifier) -> Self {
         match qos {
@@ -20,7 +43,7 @@ impl From<QoSWithPacketIdentifier> for QualityOfService {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
     use std::cmp::min;
@@ -39,4 +62,20 @@ mod test {
         let q2 = QualityOfService::Level1;
         assert_eq!(min(q1, q2), q2);
     }
+
+    #[test]
+    fn qos_from_u8() {
+        assert_eq!(QualityOfService::from_u8(0), Some(QualityOfService::Level0));
+        assert_eq!(QualityOfService::from_u8(2), Some(QualityOfService::Level2));
+        assert_eq!(QualityOfService::from_u8(3), None);
+    }
+
+    #[test]
+    fn qos_downgrade() {
+        let q = QualityOfService::Level2.downgrade(QualityOfService::Level1);
+        assert_eq!(q, QualityOfService::Level1);
+
+        let q = QualityOfService::Level0.downgrade(QualityOfService::Level2);
+        assert_eq!(q, QualityOfService::Level0);
+    }
 }
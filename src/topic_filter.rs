@@ -1,14 +1,18 @@
 //! Topic filter
 
-use std::io::{self, Read, Write};
-use std::ops::Deref;
+use alloc::{borrow::ToOwned, collections::BTreeSet, string::String, vec::Vec};
+use core::convert::TryFrom;
+use core::iter;
+use core::ops::Deref;
+use core::str::FromStr;
 
+use crate::io::{self, Read, Write};
 use crate::topic_name::TopicNameRef;
 use crate::{Decodable, Encodable};
 
 #[inline]
 fn is_invalid_topic_filter(topic: &str) -> bool {
-    if topic.is_empty() || topic.as_bytes().len() > 65535 {
+    if topic.is_empty() || topic.len() > 65535 {
         return true;
     }
 
@@ -34,6 +38,9 @@ fn is_invalid_topic_filter(topic: &str) -> bool {
 
 /// Topic filter
 ///
+/// Equality compares the filter strings exactly, like `TopicName`. Matching against topic names is
+/// case sensitive too, and a trailing `/` adds an empty level, so `a/` does not match the topic `a`.
+///
 /// <http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718106>
 ///
 /// ```rust
@@ -44,6 +51,8 @@ fn is_invalid_topic_filter(topic: &str) -> bool {
 /// assert!(matcher.is_match(TopicNameRef::new("sport/abc/player1").unwrap()));
 /// ```
 #[derive(Debug, Eq, PartialEq, Clone, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "String", into = "String"))]
 pub struct TopicFilter(String);
 
 impl TopicFilter {
@@ -69,6 +78,30 @@ impl TopicFilter {
     }
 }
 
+impl TryFrom<String> for TopicFilter {
+    type Error = TopicFilterError;
+
+    fn try_from(value: String) -> Result<TopicFilter, TopicFilterError> {
+        TopicFilter::new(value)
+    }
+}
+
+impl TryFrom<&str> for TopicFilter {
+    type Error = TopicFilterError;
+
+    fn try_from(value: &str) -> Result<TopicFilter, TopicFilterError> {
+        TopicFilter::new(value)
+    }
+}
+
+impl FromStr for TopicFilter {
+    type Err = TopicFilterError;
+
+    fn from_str(s: &str) -> Result<TopicFilter, TopicFilterError> {
+        TopicFilter::new(s)
+    }
+}
+
 impl From<TopicFilter> for String {
     fn from(topic: TopicFilter) -> String {
         topic.0
@@ -95,6 +128,12 @@ impl Decodable for TopicFilter {
     }
 }
 
+impl AsRef<str> for TopicFilter {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
 impl Deref for TopicFilter {
     type Target = TopicFilterRef;
 
@@ -132,8 +171,53 @@ impl TopicFilterRef {
     }
 
     /// Get a matcher
+    ///
+    /// For a shared subscription, the matcher matches with the underlying topic filter.
     pub fn get_matcher(&self) -> TopicFilterMatcher<'_> {
-        TopicFilterMatcher::new(&self.0)
+        match self.as_shared() {
+            Some(shared) => TopicFilterMatcher::new(&shared.filter().0),
+            None => TopicFilterMatcher::new(&self.0),
+        }
+    }
+
+    /// Splits a shared subscription, which is in the form of `$share/{ShareName}/{filter}`
+    ///
+    /// Returns `None` if this is not a shared subscription.
+    pub fn as_shared(&self) -> Option<SharedSubscription<'_>> {
+        let rest = self.0.strip_prefix(SHARED_SUBSCRIPTION_PREFIX)?;
+        let (group, filter) = rest.split_at(rest.find('/')?);
+        let filter = &filter[1..];
+
+        if group.is_empty() || group.contains(['+', '#']) || filter.is_empty() {
+            return None;
+        }
+
+        Some(SharedSubscription {
+            group,
+            // The rest of a valid filter is still a valid filter
+            filter: unsafe { TopicFilterRef::new_unchecked(filter) },
+        })
+    }
+}
+
+const SHARED_SUBSCRIPTION_PREFIX: &str = "$share/";
+
+/// A shared subscription, delivering each message to only one of the subscribers in the group
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SharedSubscription<'a> {
+    group: &'a str,
+    filter: &'a TopicFilterRef,
+}
+
+impl<'a> SharedSubscription<'a> {
+    /// Name of the share group
+    pub fn group(&self) -> &'a str {
+        self.group
+    }
+
+    /// The topic filter that is subscribed by the group
+    pub fn filter(&self) -> &'a TopicFilterRef {
+        self.filter
     }
 }
 
@@ -145,6 +229,22 @@ impl Deref for TopicFilterRef {
     }
 }
 
+impl AsRef<str> for TopicFilterRef {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Encodable for TopicFilterRef {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<(), io::Error> {
+        (&self.0[..]).encode(writer)
+    }
+
+    fn encoded_length(&self) -> u32 {
+        (&self.0[..]).encoded_length()
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error("invalid topic filter ({0})")]
 pub struct TopicFilterError(pub String);
@@ -170,8 +270,15 @@ impl<'a> TopicFilterMatcher<'a> {
 
     /// Check if this filter can match the `topic_name`
     pub fn is_match(&self, topic_name: &TopicNameRef) -> bool {
-        let mut tn_itr = topic_name.split('/');
-        let mut ft_itr = self.topic_filter.split('/');
+        self.is_match_bytes(topic_name.as_bytes())
+    }
+
+    /// Check if this filter can match the topic name in raw bytes
+    ///
+    /// Levels are compared as byte slices, `topic` is not validated as UTF-8 or as a topic name.
+    pub fn is_match_bytes(&self, topic: &[u8]) -> bool {
+        let mut tn_itr = topic.split(|&b| b == b'/');
+        let mut ft_itr = self.topic_filter.as_bytes().split(|&b| b == b'/');
 
         // The Server MUST NOT match Topic Filters starting with a wildcard character (# or +)
         // with Topic Names beginning with a $ character [MQTT-4.7.2-1].
@@ -179,15 +286,15 @@ impl<'a> TopicFilterMatcher<'a> {
         let first_ft = ft_itr.next().unwrap();
         let first_tn = tn_itr.next().unwrap();
 
-        if first_tn.starts_with('$') {
+        if first_tn.starts_with(b"$") {
             if first_tn != first_ft {
                 return false;
             }
         } else {
             match first_ft {
                 // Matches the whole topic
-                "#" => return true,
-                "+" => {}
+                b"#" => return true,
+                b"+" => {}
                 _ => {
                     if first_tn != first_ft {
                         return false;
@@ -199,8 +306,8 @@ impl<'a> TopicFilterMatcher<'a> {
         loop {
             match (ft_itr.next(), tn_itr.next()) {
                 (Some(ft), Some(tn)) => match ft {
-                    "#" => break,
-                    "+" => {}
+                    b"#" => break,
+                    b"+" => {}
                     _ => {
                         if ft != tn {
                             return false;
@@ -208,7 +315,7 @@ impl<'a> TopicFilterMatcher<'a> {
                     }
                 },
                 (Some(ft), None) => {
-                    if ft != "#" {
+                    if ft != b"#" {
                         return false;
                     } else {
                         break;
@@ -221,12 +328,75 @@ impl<'a> TopicFilterMatcher<'a> {
 
         true
     }
+
+    /// Iterates over levels of this filter and `topic` side by side, for custom matching logic
+    ///
+    /// Once one of them runs out of levels, `None` is yielded in its place until the other ends.
+    pub fn segment_pairs<'b>(
+        &'b self,
+        topic: &'b TopicNameRef,
+    ) -> impl Iterator<Item = (Option<&'b str>, Option<&'b str>)> + 'b {
+        let mut ft_itr = self.topic_filter.split('/');
+        let mut tn_itr = topic.split('/');
+
+        iter::from_fn(move || match (ft_itr.next(), tn_itr.next()) {
+            (None, None) => None,
+            pair => Some(pair),
+        })
+    }
+}
+
+/// Computes the changes needed to turn the `current` subscriptions into the `desired` ones
+///
+/// Returns filters to subscribe and filters to unsubscribe, in the order they appear in `desired`
+/// and `current` respectively.
+pub fn diff(current: &[TopicFilter], desired: &[TopicFilter]) -> (Vec<TopicFilter>, Vec<TopicFilter>) {
+    let current_set = current.iter().collect::<BTreeSet<_>>();
+    let desired_set = desired.iter().collect::<BTreeSet<_>>();
+
+    let mut seen = BTreeSet::new();
+    let to_subscribe = desired
+        .iter()
+        .filter(|filter| !current_set.contains(filter) && seen.insert(*filter))
+        .cloned()
+        .collect();
+
+    let mut seen = BTreeSet::new();
+    let to_unsubscribe = current
+        .iter()
+        .filter(|filter| !desired_set.contains(filter) && seen.insert(*filter))
+        .cloned()
+        .collect();
+
+    (to_subscribe, to_unsubscribe)
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
 
+    #[test]
+    fn topic_filter_parse() {
+        let filter: TopicFilter = "a/+".parse().unwrap();
+        assert_eq!(&filter[..], "a/+");
+        assert!("a/#/b".parse::<TopicFilter>().is_err());
+        assert!(TopicFilter::try_from("a/b").is_ok());
+    }
+
+    #[test]
+    fn topic_filter_as_ref() {
+        fn as_str<S: AsRef<str> + ?Sized>(s: &S) -> &str {
+            s.as_ref()
+        }
+
+        let filter = TopicFilter::new("a/+").unwrap();
+        assert_eq!(as_str(&filter), "a/+");
+        assert_eq!(as_str(TopicFilterRef::new("a/#").unwrap()), "a/#");
+
+        let matcher = TopicFilterRef::new("a/").unwrap().get_matcher();
+        assert!(!matcher.is_match(TopicNameRef::new("a").unwrap()));
+    }
+
     #[test]
     fn topic_filter_validate() {
         let topic = "#".to_owned();
@@ -302,4 +472,78 @@ mod test {
         let matcher = filter.get_matcher();
         assert!(matcher.is_match(TopicNameRef::new("$SYS/monitor/Clients").unwrap()));
     }
+
+    #[test]
+    fn topic_filter_matcher_segment_pairs() {
+        let filter = TopicFilter::new("a/+").unwrap();
+        let matcher = filter.get_matcher();
+        let topic = TopicNameRef::new("a/b/c").unwrap();
+
+        let pairs = matcher.segment_pairs(topic).collect::<Vec<_>>();
+        assert_eq!(
+            pairs,
+            [(Some("a"), Some("a")), (Some("+"), Some("b")), (None, Some("c"))]
+        );
+    }
+
+    #[test]
+    fn topic_filter_diff() {
+        let filters = |fs: &[&str]| fs.iter().map(|f| TopicFilter::new(*f).unwrap()).collect::<Vec<_>>();
+
+        let current = filters(&["a/b", "a/+", "c/#"]);
+        let desired = filters(&["c/#", "d", "a/b", "e/+/f"]);
+
+        let (to_subscribe, to_unsubscribe) = diff(&current, &desired);
+        assert_eq!(to_subscribe, filters(&["d", "e/+/f"]));
+        assert_eq!(to_unsubscribe, filters(&["a/+"]));
+
+        let (to_subscribe, to_unsubscribe) = diff(&current, &current);
+        assert!(to_subscribe.is_empty());
+        assert!(to_unsubscribe.is_empty());
+    }
+
+    #[test]
+    fn topic_filter_matcher_bytes() {
+        let filters = ["sport/#", "#", "+/monitor/Clients", "$SYS/#", "$SYS/monitor/+", "a/+/c"];
+        let names = [
+            "sport",
+            "/",
+            "abc/def",
+            "$SYS",
+            "$SYS/monitor/Clients",
+            "a/b/c",
+            "a/b/c/d",
+        ];
+
+        for filter in filters.iter() {
+            let filter = TopicFilter::new(*filter).unwrap();
+            let matcher = filter.get_matcher();
+            for name in names.iter() {
+                assert_eq!(
+                    matcher.is_match_bytes(name.as_bytes()),
+                    matcher.is_match(TopicNameRef::new(name).unwrap()),
+                    "{} {}",
+                    &filter[..],
+                    name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn topic_filter_shared() {
+        let filter = TopicFilter::new("$share/grp/sensor/+").unwrap();
+        let shared = filter.as_shared().unwrap();
+        assert_eq!(shared.group(), "grp");
+        assert_eq!(&shared.filter()[..], "sensor/+");
+
+        let matcher = filter.get_matcher();
+        assert!(matcher.is_match(TopicNameRef::new("sensor/kitchen").unwrap()));
+        assert!(!matcher.is_match(TopicNameRef::new("$share/grp/sensor/kitchen").unwrap()));
+
+        assert!(TopicFilter::new("sensor/+").unwrap().as_shared().is_none());
+        assert!(TopicFilter::new("$share/grp").unwrap().as_shared().is_none());
+        assert!(TopicFilter::new("$share//sensor").unwrap().as_shared().is_none());
+        assert!(TopicFilter::new("$share/+/sensor").unwrap().as_shared().is_none());
+    }
 }
@@ -1,22 +1,30 @@
 //! Topic name
 
-use std::{
+use alloc::{borrow::ToOwned, string::String};
+use core::{
     borrow::{Borrow, BorrowMut},
-    io::{self, Read, Write},
+    convert::TryFrom,
     ops::{Deref, DerefMut},
+    str::FromStr,
 };
 
+use crate::io::{self, Read, Write};
 use crate::{Decodable, Encodable};
 
 #[inline]
 fn is_invalid_topic_name(topic_name: &str) -> bool {
-    topic_name.is_empty() || topic_name.as_bytes().len() > 65535 || topic_name.chars().any(|ch| ch == '#' || ch == '+')
+    topic_name.is_empty() || topic_name.len() > 65535 || topic_name.chars().any(|ch| ch == '#' || ch == '+')
 }
 
 /// Topic name
 ///
+/// Topic names are compared as plain strings, which is case sensitive and keeps empty levels,
+/// so `a/b`, `A/b` and `a/b/` are three different topics. No normalization is ever applied.
+///
 /// <http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718106>
 #[derive(Debug, Eq, PartialEq, Clone, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "String", into = "String"))]
 pub struct TopicName(String);
 
 impl TopicName {
@@ -42,6 +50,30 @@ impl TopicName {
     }
 }
 
+impl TryFrom<String> for TopicName {
+    type Error = TopicNameError;
+
+    fn try_from(value: String) -> Result<TopicName, TopicNameError> {
+        TopicName::new(value)
+    }
+}
+
+impl TryFrom<&str> for TopicName {
+    type Error = TopicNameError;
+
+    fn try_from(value: &str) -> Result<TopicName, TopicNameError> {
+        TopicName::new(value)
+    }
+}
+
+impl FromStr for TopicName {
+    type Err = TopicNameError;
+
+    fn from_str(s: &str) -> Result<TopicName, TopicNameError> {
+        TopicName::new(s)
+    }
+}
+
 impl From<TopicName> for String {
     fn from(topic_name: TopicName) -> String {
         topic_name.0
@@ -62,6 +94,12 @@ impl DerefMut for TopicName {
     }
 }
 
+impl AsRef<str> for TopicName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
 impl Borrow<TopicNameRef> for TopicName {
     fn borrow(&self) -> &TopicNameRef {
         Deref::deref(self)
@@ -172,6 +210,12 @@ impl Deref for TopicNameRef {
     }
 }
 
+impl AsRef<str> for TopicNameRef {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
 impl ToOwned for TopicNameRef {
     type Owned = TopicName;
 
@@ -190,10 +234,30 @@ impl Encodable for TopicNameRef {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
 
+    #[test]
+    fn topic_name_parse() {
+        let topic_name: TopicName = "a/b".parse().unwrap();
+        assert_eq!(&topic_name[..], "a/b");
+        assert!("a/+".parse::<TopicName>().is_err());
+        assert!(TopicName::try_from("").is_err());
+    }
+
+    #[test]
+    fn topic_name_as_ref() {
+        fn as_str<S: AsRef<str> + ?Sized>(s: &S) -> &str {
+            s.as_ref()
+        }
+
+        let topic_name = TopicName::new("a/b/").unwrap();
+        assert_eq!(as_str(&topic_name), "a/b/");
+        assert_eq!(as_str(TopicNameRef::new("a/b").unwrap()), "a/b");
+        assert_ne!(topic_name, TopicName::new("a/b").unwrap());
+    }
+
     #[test]
     fn topic_name_sys() {
         let topic_name = "$SYS".to_owned();